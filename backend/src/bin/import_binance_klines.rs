@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use wallet_analyzer::modules::price_store::{PriceStore, DEFAULT_PRICE_STORE_DIR};
+use wallet_analyzer::modules::prices::SOLANA_MINT;
+use wallet_analyzer::modules::providers::{interval_secs, invert_candles};
+use wallet_analyzer::modules::stablecoins::{peg_fx_symbol, StablecoinRegistry};
+use wallet_analyzer::modules::types::Candle;
+use wallet_analyzer::modules::utils::load_config;
+
+/// Import Binance kline archives (data.binance.vision monthly/daily ZIP or CSV files)
+/// into the local price store.
+///
+/// Usage: import_binance_klines [--asset <SYMBOL>=<mint>]... <file-or-dir>...
+///
+/// Files must keep Binance's naming, e.g. `SOLUSDT-1m-2024-01.zip` or
+/// `SOLUSDT-1s-2024-01-15.csv`. SOLUSDT and stablecoin market pairs are stored under
+/// their mint (USDT inverted from USDCUSDT), FX pairs like EURUSDT under the symbol;
+/// `--asset` maps further symbols
+/// to mints. Files of symbols with no mapping are skipped, as nothing would read them.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
+    let registry = StablecoinRegistry::from_config(&config);
+    let store = PriceStore::open(config.price_store_dir.as_deref().unwrap_or(DEFAULT_PRICE_STORE_DIR));
+
+    // Assets each symbol is stored under, and whether the asset is the pair's quote
+    let mut symbol_assets: HashMap<String, Vec<(String, bool)>> =
+        HashMap::from([("SOLUSDT".to_string(), vec![(SOLANA_MINT.to_string(), false)])]);
+    for stable in registry.all() {
+        if let Some(symbol) = &stable.market_symbol {
+            symbol_assets
+                .entry(symbol.clone())
+                .or_default()
+                .push((stable.mint.clone(), stable.market_inverted()));
+        }
+        if let Some(symbol) = peg_fx_symbol(&stable.peg) {
+            symbol_assets.insert(symbol.to_string(), vec![(symbol.to_string(), false)]);
+        }
+    }
+
+    let mut files = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--asset" {
+            let mapping = args.next().ok_or("--asset needs <SYMBOL>=<mint>")?;
+            let (symbol, mint) = mapping
+                .split_once('=')
+                .filter(|(symbol, mint)| !symbol.is_empty() && !mint.is_empty())
+                .ok_or_else(|| format!("--asset needs <SYMBOL>=<mint>, got {}", mapping))?;
+            symbol_assets.insert(symbol.to_uppercase(), vec![(mint.to_string(), false)]);
+        } else {
+            collect_files(Path::new(&arg), &mut files)?;
+        }
+    }
+
+    if files.is_empty() {
+        return Err("Usage: import_binance_klines [--asset <SYMBOL>=<mint>]... <file-or-dir>...".into());
+    }
+    files.sort();
+
+    let mut total = 0;
+    for path in files {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let mut parts = file_name.split('-');
+        let (Some(symbol), Some(interval)) = (parts.next(), parts.next()) else {
+            println!("⚠️  Skipping {}: not a Binance kline file name", file_name);
+            continue;
+        };
+        let Some(step) = interval_secs(interval) else {
+            println!("⚠️  Skipping {}: unknown interval {}", file_name, interval);
+            continue;
+        };
+
+        let Some(assets) = symbol_assets.get(symbol) else {
+            println!("⚠️  Skipping {}: no mint known for {} (use --asset {}=<mint>)", file_name, symbol, symbol);
+            continue;
+        };
+
+        let content = read_csv(&path)?;
+        let candles = parse_klines(&content);
+        let (Some(first), Some(last)) = (candles.first(), candles.last()) else {
+            println!("⚠️  No klines in {}", file_name);
+            continue;
+        };
+        let covered = (first.open_time, last.open_time + step - 1);
+
+        for (asset, inverted) in assets {
+            let candles = if *inverted { invert_candles(candles.clone()) } else { candles.clone() };
+            let count = candles.len();
+            store.insert(asset, interval, candles, covered)?;
+            total += count;
+            println!("✅ {}: {} {} candles for {}", file_name, count, interval, asset);
+        }
+    }
+
+    println!("📦 Imported {} candles into the price store", total);
+    Ok(())
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect_files(&entry?.path(), files)?;
+        }
+    } else if matches!(path.extension().and_then(|e| e.to_str()), Some("zip" | "csv")) {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// CSV content of a plain `.csv` file or the first entry of a `.zip` archive
+fn read_csv(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    if path.extension().and_then(|e| e.to_str()) != Some("zip") {
+        return Ok(fs::read_to_string(path)?);
+    }
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let mut content = String::new();
+    archive.by_index(0)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Parse Binance kline rows: open_time, open, high, low, close, volume, ...
+/// Newer spot archives use microsecond timestamps, older ones milliseconds.
+fn parse_klines(content: &str) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            let raw_time: u64 = fields.first()?.trim().parse().ok()?;
+            let open_time = if raw_time >= 1_000_000_000_000_000 {
+                raw_time / 1_000_000
+            } else {
+                raw_time / 1000
+            };
+            Some(Candle {
+                open_time,
+                open: fields.get(1)?.parse().ok()?,
+                high: fields.get(2)?.parse().ok()?,
+                low: fields.get(3)?.parse().ok()?,
+                close: fields.get(4)?.parse().ok()?,
+                volume: fields.get(5)?.parse().ok()?,
+                source: "binance".to_string(),
+            })
+        })
+        .collect();
+    candles.sort_by_key(|c| c.open_time);
+    candles
+}
@@ -1,19 +1,33 @@
 use axum::{
-    routing::post,
+    extract::{Path, Query},
+    routing::{get, post},
     Json, Router,
 };
+use serde::Serialize;
 use serde_json::{json, Value};
 use std::net::SocketAddr;
 use tower_http::cors::{CorsLayer, Any};
-use wallet_analyzer::modules::transactions::get_transactions;
+use wallet_analyzer::modules::transactions::{analysis_window, get_transactions, DEFAULT_WINDOW};
 use wallet_analyzer::modules::swaps::filter_and_name_swaps;
 use wallet_analyzer::modules::prices::get_or_load_swaps_with_prices;
-use wallet_analyzer::modules::pnl::calc_pnl;
-use wallet_analyzer::modules::types::{PnlRequest, TokenPnl, Settings};
-use wallet_analyzer::modules::utils::{load_config};
+use wallet_analyzer::modules::pnl::{calc_pnl, compare_price_sampling, valuation_time, PnlOptions};
+use wallet_analyzer::modules::pnl_series::{parse_timezone, pnl_series};
+use wallet_analyzer::modules::fx::{apply_base_currency, base_currency};
+use wallet_analyzer::modules::resources::{matches_token, pricing_coverage, sorted_values};
+use wallet_analyzer::modules::receipts::{apply_receipt_cost_basis, incoming_receipts};
+use wallet_analyzer::modules::stats::wallet_summary;
+use wallet_analyzer::modules::tax::{disposals, income_events, tax_report, write_tax_report, TaxRules, DEFAULT_TAX_REPORT_DIR};
+use wallet_analyzer::modules::types::{BucketSize, ExplainQuery, ImpliedPriceQuery, JobStage, JobStatus, NamedSwap, PnlRequest, PnlSeriesRequest, PricedSwap, PricingCoverage, RawTxn, Receipt, ResponseMeta, TaxReportRequest, TokenPnl, Settings, Trade, WalletQuery};
+use wallet_analyzer::modules::execution::analyze_execution;
+use wallet_analyzer::modules::explain::explain_token;
+use wallet_analyzer::modules::jobs::{cancel_job, create_job, job_status, JobProgress};
+use wallet_analyzer::modules::implied_prices::{implied_candles, record_implied_prices, ImpliedPriceBook};
+use wallet_analyzer::modules::providers::interval_secs;
+use wallet_analyzer::modules::stablecoins::StablecoinRegistry;
+use wallet_analyzer::modules::utils::{get_implied_prices_path, load_config};
 
-/// Run the entire pipeline for a wallet and return enriched PnL trades
-pub async fn run_pipeline(wallet_address: String) -> Result<Vec<TokenPnl>, Box<dyn std::error::Error>> {
+/// Load config and API keys for one wallet
+fn load_settings(wallet_address: String) -> Result<Settings, Box<dyn std::error::Error>> {
     let config = load_config().map_err(|e| format!("Failed to load config: {}", e))?;
     
     dotenvy::dotenv().ok(); // loads .env if available
@@ -21,14 +35,186 @@ pub async fn run_pipeline(wallet_address: String) -> Result<Vec<TokenPnl>, Box<d
     println!("🔑 Using Helius key: {}", helius_api_key);
     let birdeye_api_key = std::env::var("birdeye_api_key").expect("Missing birdeye_api_key");
     println!("🔑 Using birdeye key: {}", birdeye_api_key);
-    let settings = Settings {
+    let stablecoins = StablecoinRegistry::from_config(&config);
+    Ok(Settings {
         config,
         helius_api_key,
         birdeye_api_key,
         wallet_address,
+        stablecoins,
+    })
+}
+
+/// Settings for a PnL request, with the request's overrides applied
+fn request_settings(request: PnlRequest) -> Result<Settings, Box<dyn std::error::Error>> {
+    let mut settings = load_settings(request.wallet_address)?;
+    if request.cost_basis_method.is_some() {
+        settings.config.cost_basis_method = request.cost_basis_method;
+    }
+    if request.lot_selections.is_some() {
+        settings.config.lot_selections = request.lot_selections;
+    }
+    if request.as_of.is_some() {
+        settings.config.as_of = request.as_of;
+    }
+    Ok(settings)
+}
+
+/// Settings for a resource route, with the query's overrides applied
+fn query_settings(wallet_address: String, query: &WalletQuery) -> Result<Settings, Box<dyn std::error::Error>> {
+    let mut settings = load_settings(wallet_address)?;
+    if query.window.is_some() {
+        settings.config.window = query.window.clone();
+    }
+    if query.cost_basis_method.is_some() {
+        settings.config.cost_basis_method = query.cost_basis_method;
+    }
+    if query.base_currency.is_some() {
+        settings.config.base_currency = query.base_currency.clone();
+    }
+    if query.as_of.is_some() {
+        settings.config.as_of = query.as_of;
+    }
+    Ok(settings)
+}
+
+/// `{ data, meta }` response of a resource route; `data` is sorted if it is a list
+fn envelope<T: Serialize>(
+    resource: &str,
+    settings: &Settings,
+    query: &WalletQuery,
+    data: &[T],
+    pricing_coverage: Option<PricingCoverage>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let (start_ts, end_ts) = analysis_window(settings)?;
+    let data = sorted_values(data, query.sort.as_deref())?;
+    let meta = ResponseMeta {
+        wallet_address: settings.wallet_address.clone(),
+        resource: resource.to_string(),
+        window: settings.config.window.clone().unwrap_or_else(|| DEFAULT_WINDOW.to_string()),
+        start_ts,
+        end_ts,
+        currency: base_currency(settings),
+        cost_basis_method: PnlOptions::from_settings(settings).method,
+        token: query.token.clone(),
+        sort: query.sort.clone(),
+        count: data.len(),
+        pricing_coverage,
+        generated_at: chrono::Utc::now().timestamp() as u64,
     };
-    
-    let transactions = match get_transactions(&settings).await {
+    Ok(json!({ "data": data, "meta": meta }))
+}
+
+/// Reply of a resource route: the envelope, or { error: ... }
+fn resource_response(result: Result<Value, Box<dyn std::error::Error>>) -> Json<Value> {
+    match result {
+        Ok(value) => Json(value),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// GET /api/wallets/:addr/transactions?window=7d&token=<mint>&sort=-timestamp
+async fn wallet_transactions(Path(wallet_address): Path<String>, Query(query): Query<WalletQuery>) -> Json<Value> {
+    let result = async {
+        let settings = query_settings(wallet_address, &query)?;
+        let mut transactions = get_transactions(&settings).await.map_err(|e| e as Box<dyn std::error::Error>)?;
+        if let Some(token) = &query.token {
+            transactions.retain(|tx| tx.token_transfers.iter().any(|t| &t.mint == token));
+        }
+        envelope("transactions", &settings, &query, &transactions, None)
+    };
+    resource_response(result.await)
+}
+
+/// GET /api/wallets/:addr/swaps?window=7d&base_currency=EUR&token=bonk&sort=-usd_value
+async fn wallet_swaps(Path(wallet_address): Path<String>, Query(query): Query<WalletQuery>) -> Json<Value> {
+    let result = async {
+        let settings = query_settings(wallet_address, &query)?;
+        let (_, _, mut priced_swaps) = price_wallet(&settings, None).await?;
+        apply_base_currency(&mut priced_swaps, &settings).await;
+        let coverage = pricing_coverage(&priced_swaps);
+        if let Some(token) = &query.token {
+            priced_swaps.retain(|s| {
+                matches_token(token, &s.sold_mint, &s.sold_token_name)
+                    || matches_token(token, &s.bought_mint, &s.bought_token_name)
+            });
+        }
+        envelope("swaps", &settings, &query, &priced_swaps, Some(coverage))
+    };
+    resource_response(result.await)
+}
+
+/// GET /api/wallets/:addr/pnl?window=90d&cost_basis_method=hifo&sort=-realized_pnl
+async fn wallet_pnl(Path(wallet_address): Path<String>, Query(query): Query<WalletQuery>) -> Json<Value> {
+    let result = async {
+        let settings = query_settings(wallet_address, &query)?;
+        let analysis = run_pipeline(&settings, None).await?;
+        let mut token_pnls = analysis.token_pnls;
+        if let Some(token) = &query.token {
+            token_pnls.retain(|t| matches_token(token, &t.mint, &t.token));
+        }
+        envelope("pnl", &settings, &query, &token_pnls, Some(pricing_coverage(&analysis.priced_swaps)))
+    };
+    resource_response(result.await)
+}
+
+/// GET /api/wallets/:addr/trades?token=<mint>&sort=-pnl, by sell time unless sorted
+async fn wallet_trades(Path(wallet_address): Path<String>, Query(query): Query<WalletQuery>) -> Json<Value> {
+    let result = async {
+        let settings = query_settings(wallet_address, &query)?;
+        let analysis = run_pipeline(&settings, None).await?;
+        let mut trades = sorted_trades(analysis.token_pnls);
+        if let Some(token) = &query.token {
+            trades.retain(|t| matches_token(token, &t.token_mint, &t.token_name));
+        }
+        envelope("trades", &settings, &query, &trades, Some(pricing_coverage(&analysis.priced_swaps)))
+    };
+    resource_response(result.await)
+}
+
+/// GET /api/wallets/:addr/summary?window=30d&token=<mint>
+async fn wallet_summary_resource(Path(wallet_address): Path<String>, Query(query): Query<WalletQuery>) -> Json<Value> {
+    let result = async {
+        let settings = query_settings(wallet_address, &query)?;
+        let tz = parse_timezone(settings.config.timezone.as_deref())?;
+        let analysis = run_pipeline(&settings, None).await?;
+        let mut token_pnls = analysis.token_pnls;
+        if let Some(token) = &query.token {
+            token_pnls.retain(|t| matches_token(token, &t.mint, &t.token));
+        }
+        let series = pnl_series(&token_pnls, BucketSize::Day, tz, valuation_time(&settings), &settings).await;
+        let summary = wallet_summary(&settings.wallet_address, &series.currency, &token_pnls, &series.equity_curve);
+        let mut response = envelope::<Value>("summary", &settings, &query, &[], Some(pricing_coverage(&analysis.priced_swaps)))?;
+        response["data"] = json!(summary);
+        response["meta"]["count"] = json!(1);
+        Ok(response)
+    };
+    resource_response(result.await)
+}
+
+/// Everything the pipeline produced for one wallet
+struct WalletAnalysis {
+    priced_swaps: Vec<PricedSwap>,
+    receipts: Vec<Receipt>,
+    token_pnls: Vec<TokenPnl>,
+}
+
+/// Fetch, name and price the wallet's swaps, reporting each stage to `progress` if given
+async fn price_wallet(
+    settings: &Settings,
+    progress: Option<&JobProgress>,
+) -> Result<(Vec<RawTxn>, Vec<NamedSwap>, Vec<PricedSwap>), Box<dyn std::error::Error>> {
+    let report = |f: &dyn Fn(&mut JobStatus)| {
+        if let Some(progress) = progress {
+            progress.update(f);
+        }
+    };
+
+    report(&|status| status.stage = JobStage::Fetching);
+    let transactions = match get_transactions(settings).await {
         Ok(t) => t,
         Err(e) => {
             eprintln!("❌ Failed to get transactions: {e}");
@@ -36,21 +222,80 @@ pub async fn run_pipeline(wallet_address: String) -> Result<Vec<TokenPnl>, Box<d
         }
     };
     println!("Total transactions fetched/loaded: {}", transactions.len());
+    report(&|status| {
+        status.stage = JobStage::Naming;
+        status.transactions = Some(transactions.len());
+    });
 
-    let named_swaps = filter_and_name_swaps(&transactions, &settings).await?;
+    // Cached named and priced swaps may reach beyond the window the transactions were fetched for
+    let (start, end) = analysis_window(settings)?;
+    let in_window = |ts: u64| start.is_none_or(|start| ts >= start) && ts <= end;
+    let mut named_swaps = filter_and_name_swaps(&transactions, settings).await?;
+    named_swaps.retain(|s| in_window(s.timestamp));
     println!("Total swaps with token names: {}", named_swaps.len());
+    report(&|status| {
+        status.stage = JobStage::Pricing;
+        status.swaps = Some(named_swaps.len());
+    });
 
-    let priced_swaps = get_or_load_swaps_with_prices(&named_swaps, &settings).await?;
+    let mut priced_swaps = get_or_load_swaps_with_prices(&named_swaps, settings).await?;
+    priced_swaps.retain(|s| in_window(s.timestamp));
+    record_implied_prices(&priced_swaps, (start, end), settings);
+    let coverage = pricing_coverage(&priced_swaps);
+    report(&|status| {
+        status.priced_swaps = Some(coverage.priced);
+        status.unpriced_swaps = Some(coverage.unpriced);
+    });
 
-    let trades_with_pnl = calc_pnl(&priced_swaps, &settings).await?;
+    Ok((transactions, named_swaps, priced_swaps))
+}
 
-    Ok(trades_with_pnl)
+/// Run the entire pipeline for a wallet and return enriched PnL trades
+async fn run_pipeline(settings: &Settings, progress: Option<&JobProgress>) -> Result<WalletAnalysis, Box<dyn std::error::Error>> {
+    let (transactions, named_swaps, priced_swaps) = price_wallet(settings, progress).await?;
+    let mut receipts = incoming_receipts(&transactions, settings);
+    apply_receipt_cost_basis(&mut receipts, settings).await;
+    if let Some(progress) = progress {
+        progress.update(|status| {
+            status.stage = JobStage::Pnl;
+            status.receipts = Some(receipts.len());
+        });
+    }
+
+    if settings.config.compare_price_sampling.unwrap_or(false) {
+        println!("{:<13} | {:>14} | {:>12} | Unpriced", "Sampling", "Realized PnL", "Delta");
+        println!("{}", "-".repeat(55));
+        for c in compare_price_sampling(&named_swaps, &receipts, settings).await {
+            println!(
+                "{:<13} | {:>14.2} | {:>+12.2} | {}",
+                c.sampling.interpolation(),
+                c.realized_pnl,
+                c.delta,
+                c.unpriced_swaps
+            );
+        }
+    }
+
+    let token_pnls = calc_pnl(&priced_swaps, &receipts, settings).await?;
+    if let Some(progress) = progress {
+        progress.update(|status| status.tokens = Some(token_pnls.len()));
+    }
+
+    Ok(WalletAnalysis {
+        priced_swaps,
+        receipts,
+        token_pnls,
+    })
 }
 
-/// POST /api/pnl { "wallet": "..." } → returns { trades: [...] } or { error: ... }
+/// POST /api/pnl { "wallet_address": "...", "cost_basis_method": "hifo", "lot_selections": {...} }
+/// → returns { trades: [...] } or { error: ... }
 async fn handle_pnl(Json(payload): Json<PnlRequest>) -> Json<Value> {
-    let wallet_address = payload.wallet_address;
-    match run_pipeline(wallet_address).await {
+    let trades = async {
+        let settings = request_settings(payload)?;
+        Ok::<_, Box<dyn std::error::Error>>(run_pipeline(&settings, None).await?.token_pnls)
+    };
+    match trades.await {
         Ok(trades) => Json(json!({ "trades": trades })),
         Err(e) => {
             eprintln!("❌ Error: {e}");
@@ -59,10 +304,246 @@ async fn handle_pnl(Json(payload): Json<PnlRequest>) -> Json<Value> {
     }
 }
 
+/// POST /api/pnl/series { "wallet_address": "...", "bucket": "day" | "week" | "month", "timezone": "Europe/Berlin", ... }
+/// → returns { currency, bucket, timezone, buckets: [...], equity_curve: [...] } or { error: ... }
+async fn handle_pnl_series(Json(payload): Json<PnlSeriesRequest>) -> Json<Value> {
+    let series = async {
+        let mut settings = request_settings(payload.pnl)?;
+        if payload.timezone.is_some() {
+            settings.config.timezone = payload.timezone;
+        }
+        let tz = parse_timezone(settings.config.timezone.as_deref())?;
+        let token_pnls = run_pipeline(&settings, None).await?.token_pnls;
+        let bucket = payload.bucket.unwrap_or_default();
+        Ok::<_, Box<dyn std::error::Error>>(
+            pnl_series(&token_pnls, bucket, tz, valuation_time(&settings), &settings).await,
+        )
+    };
+    match series.await {
+        Ok(series) => Json(json!(series)),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// POST /api/summary { "wallet_address": "...", ... } → returns wallet totals and performance
+/// statistics (win rate, profit factor, drawdown, ...) or { error: ... }
+async fn handle_summary(Json(payload): Json<PnlRequest>) -> Json<Value> {
+    let summary = async {
+        let settings = request_settings(payload)?;
+        let tz = parse_timezone(settings.config.timezone.as_deref())?;
+        let token_pnls = run_pipeline(&settings, None).await?.token_pnls;
+        let series = pnl_series(&token_pnls, BucketSize::Day, tz, valuation_time(&settings), &settings).await;
+        Ok::<_, Box<dyn std::error::Error>>(wallet_summary(
+            &settings.wallet_address,
+            &series.currency,
+            &token_pnls,
+            &series.equity_curve,
+        ))
+    };
+    match summary.await {
+        Ok(summary) => Json(json!(summary)),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// POST /api/tax { "wallet_address": "...", "year": 2024, ... } → writes the Form 8949, summary and
+/// income CSVs and returns { report, files } or { error: ... }
+async fn handle_tax(Json(payload): Json<TaxReportRequest>) -> Json<Value> {
+    let report = async {
+        let settings = request_settings(payload.pnl)?;
+        let rules = TaxRules::from_settings(&settings)?;
+        let analysis = run_pipeline(&settings, None).await?;
+        let income = income_events(&analysis.receipts, &analysis.token_pnls, &rules, &settings).await;
+        let report = tax_report(
+            &settings.wallet_address,
+            &base_currency(&settings),
+            &rules,
+            disposals(&analysis.token_pnls, &rules),
+            income,
+            payload.year,
+        );
+        let dir = settings.config.tax_report_dir.as_deref().unwrap_or(DEFAULT_TAX_REPORT_DIR);
+        let files = write_tax_report(&report, dir).map_err(|e| e.to_string())?;
+        Ok::<_, Box<dyn std::error::Error>>(json!({ "report": report, "files": files }))
+    };
+    match report.await {
+        Ok(report) => Json(report),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// GET /api/wallets/:addr/tokens/:mint/explain?cost_basis_method=hifo&as_of=...
+/// → returns every lot opened, the lots each sell consumed and the running PnL, or { error: ... }
+async fn handle_explain(
+    Path((wallet_address, mint)): Path<(String, String)>,
+    Query(query): Query<ExplainQuery>,
+) -> Json<Value> {
+    let explanation = async {
+        let settings = request_settings(PnlRequest {
+            wallet_address,
+            cost_basis_method: query.cost_basis_method,
+            lot_selections: None,
+            as_of: query.as_of,
+        })?;
+        let token_pnls = run_pipeline(&settings, None).await?.token_pnls;
+        let token = token_pnls
+            .iter()
+            .find(|t| t.mint == mint)
+            .ok_or_else(|| format!("No activity for {} in this wallet", mint))?;
+        Ok::<_, Box<dyn std::error::Error>>(explain_token(&settings.wallet_address, token))
+    };
+    match explanation.await {
+        Ok(explanation) => Json(json!(explanation)),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// GET /api/implied_prices/:mint?wallet_address=...&pooled=true&interval=1m&start=...&end=...
+/// → returns { mint, interval, pooled, points: [...], candles: [...] } or { error: ... }
+async fn handle_implied_prices(
+    Path(mint): Path<String>,
+    Query(query): Query<ImpliedPriceQuery>,
+) -> Json<Value> {
+    let interval = query.interval.unwrap_or_else(|| "1m".to_string());
+    let Some(step) = interval_secs(&interval).filter(|&step| step > 0) else {
+        return Json(json!({ "error": format!("Unknown interval {}", interval) }));
+    };
+    // Without a wallet there is nothing to filter by, so prices are pooled
+    let pooled = query.pooled.unwrap_or(false) || query.wallet_address.is_none();
+    let wallet = if pooled { None } else { query.wallet_address.as_deref() };
+
+    let book = ImpliedPriceBook::open(&get_implied_prices_path());
+    let points = book.prices(&mint, wallet, query.start.unwrap_or(0), query.end.unwrap_or(u64::MAX));
+    let candles = implied_candles(&points, step);
+
+    Json(json!({
+        "mint": mint,
+        "interval": interval,
+        "pooled": pooled,
+        "points": points,
+        "candles": candles,
+    }))
+}
+
+/// Matched trades of every token, ordered by sell time
+fn sorted_trades(token_pnls: Vec<TokenPnl>) -> Vec<Trade> {
+    let mut trades: Vec<Trade> = token_pnls.into_iter().flat_map(|t| t.trades).collect();
+    trades.sort_by(|a, b| {
+        a.sell_timestamp
+            .cmp(&b.sell_timestamp)
+            .then(a.sell_signature.cmp(&b.sell_signature))
+    });
+    trades
+}
+
+/// POST /api/trades { "wallet_address": "...", ... } → returns { trades: [...] } with one entry
+/// per matched buy lot and sell, ordered by sell time, or { error: ... }
+async fn handle_trades(Json(payload): Json<PnlRequest>) -> Json<Value> {
+    let token_pnls = async {
+        let settings = request_settings(payload)?;
+        Ok::<_, Box<dyn std::error::Error>>(run_pipeline(&settings, None).await?.token_pnls)
+    };
+    match token_pnls.await {
+        Ok(token_pnls) => Json(json!({ "trades": sorted_trades(token_pnls) })),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// POST /api/execution { "wallet_address": "...", ...same options as /api/pnl }
+/// → returns the execution quality report or { error: ... }
+async fn handle_execution(Json(payload): Json<PnlRequest>) -> Json<Value> {
+    let report = async {
+        let settings = request_settings(payload)?;
+        let (_, _, priced_swaps) = price_wallet(&settings, None).await?;
+        Ok::<_, Box<dyn std::error::Error>>(analyze_execution(&priced_swaps, &settings).await)
+    };
+    match report.await {
+        Ok(report) => Json(json!(report)),
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            Json(json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+/// POST /api/jobs { "wallet_address": "...", ...same options as /api/pnl }
+/// → starts the pipeline in the background and returns { job_id, status } or { error: ... }
+async fn handle_create_job(Json(payload): Json<PnlRequest>) -> Json<Value> {
+    let settings = match request_settings(payload) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("❌ Error: {e}");
+            return Json(json!({ "error": e.to_string() }));
+        }
+    };
+
+    let progress = create_job(&settings.wallet_address);
+    let job_id = progress.id().to_string();
+    let task = tokio::spawn(async move {
+        let result = match run_pipeline(&settings, Some(&progress)).await {
+            Ok(analysis) => Ok(json!({ "trades": analysis.token_pnls })),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = &result {
+            eprintln!("❌ Job {} failed: {e}", progress.id());
+        }
+        progress.finish(result);
+    });
+
+    let progress = JobProgress::for_job(&job_id);
+    progress.set_abort_handle(task.abort_handle());
+    Json(json!({ "job_id": job_id, "status": job_status(&job_id) }))
+}
+
+/// GET /api/jobs/:id → { stage, counters, result | error, ... } or { error: ... }
+async fn handle_job_status(Path(id): Path<String>) -> Json<Value> {
+    match job_status(&id) {
+        Some(status) => Json(json!(status)),
+        None => Json(json!({ "error": format!("Unknown job {}", id) })),
+    }
+}
+
+/// DELETE /api/jobs/:id → cancels the job and returns its status, or { error: ... }
+async fn handle_cancel_job(Path(id): Path<String>) -> Json<Value> {
+    match cancel_job(&id) {
+        Some(status) => Json(json!(status)),
+        None => Json(json!({ "error": format!("Unknown job {}", id) })),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let app = Router::new()
         .route("/api/pnl", post(handle_pnl))
+        .route("/api/pnl/series", post(handle_pnl_series))
+        .route("/api/summary", post(handle_summary))
+        .route("/api/trades", post(handle_trades))
+        .route("/api/execution", post(handle_execution))
+        .route("/api/tax", post(handle_tax))
+        .route("/api/implied_prices/:mint", get(handle_implied_prices))
+        .route("/api/jobs", post(handle_create_job))
+        .route("/api/jobs/:id", get(handle_job_status).delete(handle_cancel_job))
+        .route("/api/wallets/:addr/transactions", get(wallet_transactions))
+        .route("/api/wallets/:addr/swaps", get(wallet_swaps))
+        .route("/api/wallets/:addr/pnl", get(wallet_pnl))
+        .route("/api/wallets/:addr/trades", get(wallet_trades))
+        .route("/api/wallets/:addr/summary", get(wallet_summary_resource))
+        .route("/api/wallets/:addr/tokens/:mint/explain", get(handle_explain))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -0,0 +1,222 @@
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use crate::modules::types::{RawTokenAmount, RawTxn, TokenTransfer};
+
+/// Decimals assumed for amounts whose mint has no known decimals (as for SOL)
+pub const FALLBACK_DECIMALS: u8 = 9;
+
+/// Token amount in raw integer base units. Amounts with different `decimals` are
+/// rescaled to the larger one before being combined. Serialized as the UI amount.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenAmount {
+    pub raw: u128,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(raw: u128, decimals: u8) -> Self {
+        TokenAmount { raw, decimals }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        TokenAmount { raw: 0, decimals }
+    }
+
+    /// Raw amount for a UI amount, rounded to the nearest base unit.
+    /// Negative or non-finite amounts become zero.
+    pub fn from_ui(amount: f64, decimals: Option<u8>) -> Self {
+        let decimals = decimals.unwrap_or(FALLBACK_DECIMALS);
+        let raw = Decimal::from_f64(amount)
+            .and_then(|d| d.checked_mul(Decimal::from(10u64.checked_pow(decimals as u32)?)))
+            .map(|d| d.round())
+            .and_then(|d| d.to_u128())
+            .unwrap_or(0);
+        TokenAmount { raw, decimals }
+    }
+
+    /// Absolute amount of a Helius raw amount; `None` if it does not parse
+    pub fn from_raw(raw: &RawTokenAmount) -> Option<Self> {
+        let amount = raw.token_amount.trim().trim_start_matches('-').parse::<u128>().ok()?;
+        Some(TokenAmount::new(amount, raw.decimals))
+    }
+
+    pub fn to_raw(&self) -> RawTokenAmount {
+        RawTokenAmount {
+            token_amount: self.raw.to_string(),
+            decimals: self.decimals,
+        }
+    }
+
+    /// Exact amount of a swap leg or receipt: the raw amount if the transaction
+    /// reported one, else the UI amount at the registry's decimals
+    pub fn of_leg(raw: Option<&RawTokenAmount>, ui_amount: f64, decimals: Option<u8>) -> Self {
+        raw.and_then(TokenAmount::from_raw)
+            .unwrap_or_else(|| TokenAmount::from_ui(ui_amount, decimals))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw == 0
+    }
+
+    fn scaled(&self, decimals: u8) -> u128 {
+        self.raw.saturating_mul(10u128.saturating_pow((decimals - self.decimals) as u32))
+    }
+
+    /// Raw values of both amounts at the larger of their decimals
+    fn aligned(self, other: Self) -> (u128, u128, u8) {
+        let decimals = self.decimals.max(other.decimals);
+        (self.scaled(decimals), other.scaled(decimals), decimals)
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        let (a, b, decimals) = self.aligned(other);
+        TokenAmount::new(a.saturating_sub(b), decimals)
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        if self <= other { self } else { other }
+    }
+
+    /// `self * numerator / denominator`, rounded down to a whole base unit
+    pub fn mul_ratio(self, numerator: Self, denominator: Self) -> Self {
+        if denominator.is_zero() {
+            return TokenAmount::zero(self.decimals);
+        }
+        let raw = Decimal::from_u128(self.raw)
+            .and_then(|raw| raw.checked_mul(numerator.to_decimal()))
+            .and_then(|raw| raw.checked_div(denominator.to_decimal()))
+            .and_then(|raw| raw.floor().to_u128())
+            .unwrap_or(0)
+            .min(self.raw);
+        TokenAmount::new(raw, self.decimals)
+    }
+
+    /// Exact UI amount
+    pub fn to_decimal(&self) -> Decimal {
+        Decimal::try_from_i128_with_scale(self.raw as i128, self.decimals as u32)
+            .unwrap_or(Decimal::MAX)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_decimal().to_f64().unwrap_or(0.0)
+    }
+}
+
+impl PartialEq for TokenAmount {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TokenAmount {}
+
+impl PartialOrd for TokenAmount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TokenAmount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.aligned(*other);
+        a.cmp(&b)
+    }
+}
+
+impl Add for TokenAmount {
+    type Output = TokenAmount;
+
+    fn add(self, other: Self) -> Self {
+        let (a, b, decimals) = self.aligned(other);
+        TokenAmount::new(a.saturating_add(b), decimals)
+    }
+}
+
+impl AddAssign for TokenAmount {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for TokenAmount {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(TokenAmount::zero(0), Add::add)
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_decimal().normalize(), f)
+    }
+}
+
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+/// Fixed-point value of an `f64` from a price or FX source, zero if not representable
+pub fn to_decimal(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default()
+}
+
+/// Raw amount of a token transfer: its `rawTokenAmount` if present, else its UI amount at the
+/// decimals the transaction's token balance changes report for the mint
+pub fn transfer_raw_amount(tx: &RawTxn, transfer: &TokenTransfer) -> Option<RawTokenAmount> {
+    if let Some(raw) = &transfer.raw_token_amount {
+        return Some(raw.clone());
+    }
+    let decimals = tx
+        .account_data
+        .iter()
+        .flat_map(|a| &a.token_balance_changes)
+        .find(|c| c.mint == transfer.mint)?
+        .raw_token_amount
+        .decimals;
+    Some(TokenAmount::from_ui(transfer.token_amount, Some(decimals)).to_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_decimals_are_aligned_to_the_larger() {
+        let a = TokenAmount::new(1_500_000, 6);
+        let b = TokenAmount::new(250_000_000, 9);
+
+        let sum = a + b;
+        assert_eq!((sum.raw, sum.decimals), (1_750_000_000, 9));
+        assert_eq!(a, TokenAmount::new(1_500_000_000, 9));
+        assert!(b < a);
+
+        let rest = a.saturating_sub(b);
+        assert_eq!((rest.raw, rest.decimals), (1_250_000_000, 9));
+        assert!(b.saturating_sub(a).is_zero());
+    }
+
+    #[test]
+    fn mul_ratio_rounds_down_to_a_base_unit() {
+        let lot = TokenAmount::new(10, 0);
+        let third = lot.mul_ratio(TokenAmount::new(1, 0), TokenAmount::new(3, 0));
+        assert_eq!(third.raw, 3);
+        assert!(lot.mul_ratio(lot, TokenAmount::zero(0)).is_zero());
+        // Never more than the amount itself
+        assert_eq!(lot.mul_ratio(TokenAmount::new(5, 0), TokenAmount::new(4, 0)).raw, 10);
+    }
+
+    #[test]
+    fn ui_amounts_convert_exactly() {
+        let amount = TokenAmount::from_ui(0.1, Some(6));
+        assert_eq!(amount.raw, 100_000);
+        assert_eq!(amount.to_decimal(), Decimal::new(1, 1));
+        assert_eq!(TokenAmount::from_ui(-1.0, Some(6)).raw, 0);
+        assert_eq!(TokenAmount::from_ui(1.0, None).decimals, FALLBACK_DECIMALS);
+    }
+}
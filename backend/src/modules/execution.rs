@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use crate::modules::fx::{base_currency, usd_rates_at};
+use crate::modules::prices::market_prices;
+use crate::modules::types::{ExecutionReport, PricedLeg, PricedSwap, Settings, SlippageSummary, SwapExecution};
+
+const DEFAULT_SLIPPAGE_THRESHOLD_BPS: f64 = 100.0;
+
+/// The traded token of a priced swap: the leg whose price is implied by the other one
+struct TradedLeg<'a> {
+    mint: &'a str,
+    token_name: &'a str,
+    amount: f64,
+    side: &'static str,
+}
+
+fn traded_leg(swap: &PricedSwap) -> Option<TradedLeg<'_>> {
+    match swap.priced_leg? {
+        PricedLeg::Sold => Some(TradedLeg {
+            mint: &swap.bought_mint,
+            token_name: &swap.bought_token_name,
+            amount: swap.bought_amount,
+            side: "buy",
+        }),
+        PricedLeg::Bought => Some(TradedLeg {
+            mint: &swap.sold_mint,
+            token_name: &swap.sold_token_name,
+            amount: swap.sold_amount,
+            side: "sell",
+        }),
+    }
+}
+
+/// Compare each swap's execution price with the market price of its traded token.
+/// Swaps priced from implied prices are skipped since their "market" is the swap itself.
+/// Prices and slippage are in the base currency at the rate of the swap's day.
+pub async fn analyze_execution(swaps: &[PricedSwap], settings: &Settings) -> ExecutionReport {
+    let threshold_bps = settings
+        .config
+        .slippage_threshold_bps
+        .unwrap_or(DEFAULT_SLIPPAGE_THRESHOLD_BPS);
+
+    let measured: Vec<(&PricedSwap, TradedLeg, f64)> = swaps
+        .iter()
+        .filter(|swap| !swap.pricing_method.starts_with("implied"))
+        .filter_map(|swap| {
+            let usd_value = swap.usd_value?;
+            let leg = traded_leg(swap).filter(|leg| leg.amount > 0.0)?;
+            Some((swap, leg, usd_value))
+        })
+        .collect();
+
+    let points: Vec<(String, u64)> = measured
+        .iter()
+        .map(|(swap, leg, _)| (leg.mint.to_string(), swap.timestamp))
+        .collect();
+    let prices = market_prices(&points, settings).await;
+    let currency = base_currency(settings);
+    let timestamps: Vec<u64> = points.iter().map(|(_, ts)| *ts).collect();
+    let rates = usd_rates_at(&currency, &timestamps, settings).await;
+
+    let mut executions = vec![];
+    let mut unmeasured_signatures = vec![];
+    for (((swap, leg, usd_value), market_price), rate) in measured.into_iter().zip(prices).zip(rates) {
+        let (Some(market_price), Some(rate)) = (market_price.filter(|p| *p > 0.0), rate) else {
+            unmeasured_signatures.push(swap.signature.clone());
+            continue;
+        };
+        let value = usd_value * rate;
+        let market_price = market_price * rate;
+        let execution_price = value / leg.amount;
+        let market_value = leg.amount * market_price;
+        // Buying above or selling below the market is a cost
+        let slippage = match leg.side {
+            "buy" => value - market_value,
+            _ => market_value - value,
+        };
+        let slippage_bps = slippage / market_value * 10_000.0;
+
+        executions.push(SwapExecution {
+            signature: swap.signature.clone(),
+            timestamp: swap.timestamp,
+            venue: swap.venue.clone(),
+            mint: leg.mint.to_string(),
+            token_name: leg.token_name.to_string(),
+            side: leg.side.to_string(),
+            amount: leg.amount,
+            execution_price,
+            market_price,
+            slippage_bps,
+            slippage,
+            flagged: slippage_bps > threshold_bps,
+        });
+    }
+
+    let flagged = executions.iter().filter(|e| e.flagged).count();
+    println!(
+        "🎯 Measured execution of {} swaps, {} above {} bps, {} without a market price or {} rate",
+        executions.len(),
+        flagged,
+        threshold_bps,
+        unmeasured_signatures.len(),
+        currency
+    );
+
+    ExecutionReport {
+        currency,
+        threshold_bps,
+        by_token: summarize(&executions, |e| e.token_name.clone()),
+        by_venue: summarize(&executions, |e| {
+            if e.venue.is_empty() {
+                "UNKNOWN".to_string()
+            } else {
+                e.venue.clone()
+            }
+        }),
+        by_month: summarize(&executions, |e| {
+            chrono::DateTime::from_timestamp(e.timestamp as i64, 0)
+                .map(|dt| dt.format("%Y-%m").to_string())
+                .unwrap_or_default()
+        }),
+        swaps: executions,
+        unmeasured_signatures,
+    }
+}
+
+/// Aggregate executions by `key`, sorted by key
+fn summarize(executions: &[SwapExecution], key: impl Fn(&SwapExecution) -> String) -> Vec<SlippageSummary> {
+    let mut groups: BTreeMap<String, SlippageSummary> = BTreeMap::new();
+
+    for execution in executions {
+        let key = key(execution);
+        let summary = groups.entry(key.clone()).or_insert_with(|| SlippageSummary {
+            key,
+            swaps: 0,
+            flagged: 0,
+            market_value: 0.0,
+            slippage: 0.0,
+            avg_slippage_bps: 0.0,
+            max_slippage_bps: f64::MIN,
+        });
+        summary.swaps += 1;
+        summary.flagged += execution.flagged as usize;
+        summary.market_value += execution.amount * execution.market_price;
+        summary.slippage += execution.slippage;
+        summary.max_slippage_bps = summary.max_slippage_bps.max(execution.slippage_bps);
+    }
+
+    groups
+        .into_values()
+        .map(|mut summary| {
+            if summary.market_value > 0.0 {
+                summary.avg_slippage_bps = summary.slippage / summary.market_value * 10_000.0;
+            }
+            summary
+        })
+        .collect()
+}
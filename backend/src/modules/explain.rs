@@ -0,0 +1,96 @@
+use rust_decimal::Decimal;
+use crate::modules::amounts::TokenAmount;
+use crate::modules::types::{ExplainStep, TokenExplanation, TokenPnl, Trade};
+
+/// Replay the ledger of `token`: every lot opened, the lots each sell consumed with their
+/// cost basis, and the running realized PnL and open position after each step
+pub fn explain_token(wallet_address: &str, token: &TokenPnl) -> TokenExplanation {
+    // `buys` and `sells` are each in ledger order; merge them the way the ledger walked
+    // them: receipts open their lots before any swap at the same time, swaps go by
+    // time, then signature
+    let mut events: Vec<(u8, usize)> = Vec::with_capacity(token.buys.len() + token.sells.len());
+    let (mut next_buy, mut next_sell) = (0, 0);
+    while next_buy < token.buys.len() || next_sell < token.sells.len() {
+        let buy_first = match (token.buys.get(next_buy), token.sells.get(next_sell)) {
+            (Some(buy), Some(sell)) => {
+                if buy.receipt.is_some() {
+                    buy.timestamp <= sell.timestamp
+                } else {
+                    (buy.timestamp, &buy.signature) < (sell.timestamp, &sell.signature)
+                }
+            }
+            (buy, _) => buy.is_some(),
+        };
+        if buy_first {
+            events.push((0, next_buy));
+            next_buy += 1;
+        } else {
+            events.push((1, next_sell));
+            next_sell += 1;
+        }
+    }
+
+    let mut open_amount = TokenAmount::default();
+    let mut open_cost = Decimal::ZERO;
+    let mut realized_pnl = Decimal::ZERO;
+    let mut steps = vec![];
+
+    for (kind, index) in events {
+        if kind == 0 {
+            let lot = &token.buys[index];
+            open_amount += lot.amount;
+            open_cost += lot.cost;
+            steps.push(ExplainStep::Buy {
+                unit_cost: lot.cost.checked_div(lot.amount.to_decimal()).unwrap_or_default(),
+                lot: lot.clone(),
+                open_amount,
+                open_cost,
+            });
+            continue;
+        }
+
+        let sell = &token.sells[index];
+        let lots: Vec<Trade> = token
+            .trades
+            .iter()
+            .filter(|t| t.sell_signature == sell.signature)
+            .cloned()
+            .collect();
+        let cost_basis: Decimal = lots.iter().map(|t| t.cost).sum();
+        let matched: TokenAmount = lots.iter().map(|t| t.amount).sum();
+        // Proceeds of an unmatched remainder are not part of the PnL
+        let pnl = lots.iter().map(|t| t.pnl).sum::<Decimal>();
+        realized_pnl += pnl;
+        open_amount = open_amount.saturating_sub(matched);
+        open_cost -= cost_basis;
+
+        steps.push(ExplainStep::Sell {
+            timestamp: sell.timestamp,
+            signature: sell.signature.clone(),
+            amount: sell.amount,
+            proceeds: sell.proceeds,
+            lots,
+            cost_basis,
+            pnl,
+            realized_pnl,
+            open_amount,
+            open_cost,
+        });
+    }
+
+    TokenExplanation {
+        wallet_address: wallet_address.to_string(),
+        mint: token.mint.clone(),
+        token: token.token.clone(),
+        currency: token.currency.clone(),
+        cost_basis_method: token.cost_basis_method,
+        steps,
+        realized_pnl: token.realized_pnl,
+        remaining_amount: token.remaining_amount,
+        open_cost: token.open_cost,
+        unrealized_pnl: token.unrealized_pnl,
+        unpriced_signatures: token.unpriced_signatures.clone(),
+        unmatched_disposals: token.unmatched_disposals.clone(),
+        unmatched_proceeds: token.unmatched_proceeds,
+    }
+}
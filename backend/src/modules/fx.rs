@@ -0,0 +1,291 @@
+use async_trait::async_trait;
+use chrono::{NaiveDate, TimeZone, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::modules::price_store::{PriceStore, StoreStats, DEFAULT_PRICE_STORE_DIR};
+use crate::modules::providers::{PriceSeries, RateLimiter};
+use crate::modules::transactions::AnyError;
+use crate::modules::types::{Candle, PricedSwap, Settings};
+
+const DEFAULT_FX_PROVIDER_ORDER: &[&str] = &["local", "ecb"];
+const DEFAULT_LOCAL_FX_DIR: &str = "data/fx";
+const DAY_SECS: u64 = 86400;
+/// How far back to look for the last published rate (weekends, holidays)
+const FX_LOOKBACK_SECS: u64 = 7 * DAY_SECS;
+
+/// Source of historical USD exchange rates
+#[async_trait]
+pub trait FxProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Daily rates as units of `currency` per 1 USD, one candle per UTC day.
+    /// Returns `Ok(None)` if the currency is not covered.
+    async fn usd_rates(
+        &self,
+        currency: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError>;
+}
+
+fn rate_candle(day_start: u64, rate: f64, source: &str) -> Candle {
+    Candle {
+        open_time: day_start,
+        open: rate,
+        high: rate,
+        low: rate,
+        close: rate,
+        volume: 0.0,
+        source: source.to_string(),
+    }
+}
+
+fn day_start(date: NaiveDate) -> u64 {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).timestamp() as u64
+}
+
+fn to_date(ts: u64) -> NaiveDate {
+    chrono::DateTime::from_timestamp(ts as i64, 0)
+        .map(|dt| dt.date_naive())
+        .unwrap_or_default()
+}
+
+/// ECB reference rates via the Frankfurter API
+pub struct EcbFxProvider {
+    client: Client,
+    limiter: Arc<RateLimiter>,
+}
+
+impl EcbFxProvider {
+    pub fn new(client: Client, limiter: Arc<RateLimiter>) -> Self {
+        EcbFxProvider { client, limiter }
+    }
+}
+
+#[async_trait]
+impl FxProvider for EcbFxProvider {
+    fn name(&self) -> &str {
+        "ecb"
+    }
+
+    async fn usd_rates(
+        &self,
+        currency: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        let url = format!(
+            "https://api.frankfurter.app/{}..{}?from=USD&to={}",
+            to_date(start_ts),
+            to_date(end_ts),
+            currency
+        );
+        self.limiter.wait().await;
+        let resp: Value = self.client
+            .get(&url)
+            .timeout(Duration::from_secs(10))
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        let Some(rates) = resp.get("rates").and_then(|r| r.as_object()) else {
+            return Ok(None);
+        };
+
+        let mut candles: Vec<Candle> = rates
+            .iter()
+            .filter_map(|(date, rate)| {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+                let rate = rate.get(currency)?.as_f64()?;
+                Some(rate_candle(day_start(date), rate, self.name()))
+            })
+            .collect();
+        candles.sort_by_key(|c| c.open_time);
+
+        if candles.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(candles))
+    }
+}
+
+/// Rates from `<dir>/USD_<CURRENCY>.csv` with rows `date,rate`, where `date` is
+/// `YYYY-MM-DD` or a unix timestamp and `rate` is units of the currency per 1 USD
+pub struct LocalCsvFxProvider {
+    dir: String,
+}
+
+impl LocalCsvFxProvider {
+    pub fn new(dir: String) -> Self {
+        LocalCsvFxProvider { dir }
+    }
+}
+
+#[async_trait]
+impl FxProvider for LocalCsvFxProvider {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn usd_rates(
+        &self,
+        currency: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        let path = Path::new(&self.dir).join(format!("USD_{}.csv", currency));
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+
+        let mut candles: Vec<Candle> = content
+            .lines()
+            .filter_map(|line| {
+                let (date, rate) = line.split_once(',')?;
+                let date = date.trim();
+                let ts = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                    Ok(date) => day_start(date),
+                    Err(_) => date.parse::<u64>().ok()?,
+                };
+                let rate = rate.trim().parse::<f64>().ok()?;
+                Some(rate_candle(ts, rate, self.name()))
+            })
+            .filter(|c| c.open_time >= start_ts && c.open_time <= end_ts)
+            .collect();
+        candles.sort_by_key(|c| c.open_time);
+
+        if candles.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(candles))
+    }
+}
+
+/// Tries each FX provider in the configured order until one has rates
+pub struct CompositeFxProvider {
+    providers: Vec<Box<dyn FxProvider>>,
+}
+
+impl CompositeFxProvider {
+    /// Build the chain from `fx_provider_order` in config
+    pub fn from_settings(settings: &Settings) -> Self {
+        let offline = settings.config.offline_pricing.unwrap_or(false);
+        let order = settings.config.fx_provider_order.clone().unwrap_or_else(|| {
+            DEFAULT_FX_PROVIDER_ORDER.iter().map(|p| p.to_string()).collect()
+        });
+
+        let mut providers: Vec<Box<dyn FxProvider>> = vec![];
+        for name in order {
+            match name.as_str() {
+                "local" => {
+                    let dir = settings
+                        .config
+                        .local_fx_dir
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_LOCAL_FX_DIR.to_string());
+                    providers.push(Box::new(LocalCsvFxProvider::new(dir)));
+                }
+                "ecb" if offline => {}
+                "ecb" => providers.push(Box::new(EcbFxProvider::new(
+                    Client::new(),
+                    RateLimiter::shared("ecb", settings),
+                ))),
+                other => println!("⚠️  Unknown FX provider in fx_provider_order: {}", other),
+            }
+        }
+
+        CompositeFxProvider { providers }
+    }
+
+    pub async fn usd_rates(&self, currency: &str, start_ts: u64, end_ts: u64) -> Option<PriceSeries> {
+        for provider in &self.providers {
+            match provider.usd_rates(currency, start_ts, end_ts).await {
+                Ok(Some(candles)) if !candles.is_empty() => {
+                    return Some(PriceSeries {
+                        interval: "1d".to_string(),
+                        candles,
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => println!("⚠️  {} FX provider failed for {}: {}", provider.name(), currency, e),
+            }
+        }
+        None
+    }
+}
+
+/// Configured reporting currency, upper-cased ("USD" if unset)
+pub fn base_currency(settings: &Settings) -> String {
+    settings
+        .config
+        .base_currency
+        .as_deref()
+        .unwrap_or("USD")
+        .to_uppercase()
+}
+
+/// Units of `currency` per 1 USD at each timestamp, from the last published daily rate
+pub async fn usd_rates_at(currency: &str, timestamps: &[u64], settings: &Settings) -> Vec<Option<f64>> {
+    if currency == "USD" {
+        return vec![Some(1.0); timestamps.len()];
+    }
+    let (Some(&min_ts), Some(&max_ts)) = (timestamps.iter().min(), timestamps.iter().max()) else {
+        return vec![];
+    };
+
+    let providers = CompositeFxProvider::from_settings(settings);
+    let store = PriceStore::open(
+        settings.config.price_store_dir.as_deref().unwrap_or(DEFAULT_PRICE_STORE_DIR),
+    );
+    let mut stats = StoreStats::default();
+    let start_ts = (min_ts - min_ts % DAY_SECS).saturating_sub(FX_LOOKBACK_SECS);
+    let asset = format!("fx_USD_{}", currency);
+    let rates = store
+        .candles(&asset, "1d", start_ts, max_ts, &mut stats, |from, to| {
+            providers.usd_rates(currency, from, to)
+        })
+        .await
+        .map(|s| s.candles)
+        .unwrap_or_default();
+
+    timestamps
+        .iter()
+        .map(|&ts| {
+            rates
+                .iter()
+                .rev()
+                .find(|c| c.open_time <= ts && ts - c.open_time <= FX_LOOKBACK_SECS)
+                .map(|c| c.close)
+        })
+        .collect()
+}
+
+/// Convert every swap's `usd_value` into the base currency at the rate of the swap's day.
+/// Swaps without a rate keep `base_value: None` and get an `unpriced_reason`.
+pub async fn apply_base_currency(swaps: &mut [PricedSwap], settings: &Settings) {
+    let currency = base_currency(settings);
+    let timestamps: Vec<u64> = swaps.iter().map(|s| s.timestamp).collect();
+    let rates = usd_rates_at(&currency, &timestamps, settings).await;
+
+    for (swap, rate) in swaps.iter_mut().zip(rates) {
+        swap.base_currency = currency.clone();
+        swap.fx_rate = rate;
+        swap.base_value = match (swap.usd_value, rate) {
+            (Some(usd_value), Some(rate)) => Some(usd_value * rate),
+            (Some(_), None) => {
+                swap.unpriced_reason = Some(format!("no USD/{} rate for this day", currency));
+                None
+            }
+            (None, _) => None,
+        };
+    }
+
+    let missing = swaps.iter().filter(|s| s.usd_value.is_some() && s.base_value.is_none()).count();
+    if missing > 0 {
+        println!("⚠️  {} swaps have no USD/{} rate and stay unpriced", missing, currency);
+    }
+}
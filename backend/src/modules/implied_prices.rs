@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use crate::modules::prices::SOLANA_MINT;
+use crate::modules::providers::{interval_secs, PriceProvider};
+use crate::modules::stablecoins::StablecoinRegistry;
+use crate::modules::transactions::AnyError;
+use crate::modules::types::{Candle, ImpliedPrice, PricedLeg, PricedSwap, Settings};
+use crate::modules::utils::get_implied_prices_path;
+
+/// Prices implied by the swaps of every analyzed wallet, keyed by mint.
+/// One instance per file is shared by every wallet and request in the process.
+pub struct ImpliedPriceBook {
+    path: PathBuf,
+    prices: Mutex<HashMap<String, Vec<ImpliedPrice>>>,
+}
+
+impl ImpliedPriceBook {
+    /// Shared book stored at `path`, loaded on first use
+    pub fn open(path: &str) -> Arc<ImpliedPriceBook> {
+        static BOOKS: OnceLock<Mutex<HashMap<PathBuf, Arc<ImpliedPriceBook>>>> = OnceLock::new();
+
+        let path = PathBuf::from(path);
+        let mut books = BOOKS.get_or_init(Default::default).lock().unwrap();
+        books
+            .entry(path.clone())
+            .or_insert_with(|| {
+                let prices = load_book(&path);
+                Arc::new(ImpliedPriceBook {
+                    path,
+                    prices: Mutex::new(prices),
+                })
+            })
+            .clone()
+    }
+
+    /// Replace the prices previously recorded for `wallet` in `[start, end]` (`start` is
+    /// `None` for its whole history) and persist the book. Prices outside the window are
+    /// kept. The file is written while the book is locked, so concurrent records are saved
+    /// in the order they were applied.
+    pub fn record(
+        &self,
+        wallet: &str,
+        (start, end): (Option<u64>, u64),
+        prices: Vec<ImpliedPrice>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let in_window = |ts: u64| start.is_none_or(|start| ts >= start) && ts <= end;
+        let mut book = self.prices.lock().unwrap();
+        for points in book.values_mut() {
+            points.retain(|p| p.wallet != wallet || !in_window(p.timestamp));
+        }
+        for price in prices {
+            book.entry(price.mint.clone()).or_default().push(price);
+        }
+        book.retain(|_, points| !points.is_empty());
+        for points in book.values_mut() {
+            points.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.signature.cmp(&b.signature)));
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written to a temporary file first so readers never see a partial book
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&*book)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Prices for `mint` in `[start_ts, end_ts]`, from one wallet or pooled across all
+    pub fn prices(&self, mint: &str, wallet: Option<&str>, start_ts: u64, end_ts: u64) -> Vec<ImpliedPrice> {
+        let book = self.prices.lock().unwrap();
+        book.get(mint)
+            .map(|points| {
+                points
+                    .iter()
+                    .filter(|p| p.timestamp >= start_ts && p.timestamp <= end_ts)
+                    .filter(|p| wallet.is_none_or(|w| p.wallet == w))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn load_book(path: &Path) -> HashMap<String, Vec<ImpliedPrice>> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// USD prices implied for the unpriced leg of every priced swap.
+/// Stables and SOL are skipped since external prices always cover them, and so are
+/// swaps that were themselves priced from implied prices.
+pub fn implied_prices(swaps: &[PricedSwap], wallet: &str, stablecoins: &StablecoinRegistry) -> Vec<ImpliedPrice> {
+    swaps
+        .iter()
+        .filter(|swap| !swap.pricing_method.starts_with("implied"))
+        .filter_map(|swap| {
+            let usd_value = swap.usd_value?;
+            let (mint, amount) = match swap.priced_leg? {
+                PricedLeg::Sold => (&swap.bought_mint, swap.bought_amount),
+                PricedLeg::Bought => (&swap.sold_mint, swap.sold_amount),
+            };
+            if amount <= 0.0 || mint == SOLANA_MINT || stablecoins.is_stable(mint) {
+                return None;
+            }
+            Some(ImpliedPrice {
+                mint: mint.clone(),
+                timestamp: swap.timestamp,
+                signature: swap.signature.clone(),
+                wallet: wallet.to_string(),
+                usd_price: usd_value / amount,
+                amount,
+                source_method: swap.pricing_method.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Bucket implied prices into candles of `step` seconds; volume is the token amount.
+/// A zero `step` yields no candles.
+pub fn implied_candles(prices: &[ImpliedPrice], step: u64) -> Vec<Candle> {
+    if step == 0 {
+        return Vec::new();
+    }
+    let mut buckets: BTreeMap<u64, Candle> = BTreeMap::new();
+    for price in prices {
+        let open_time = price.timestamp - price.timestamp % step;
+        buckets
+            .entry(open_time)
+            .and_modify(|c| {
+                c.high = c.high.max(price.usd_price);
+                c.low = c.low.min(price.usd_price);
+                c.close = price.usd_price;
+                c.volume += price.amount;
+            })
+            .or_insert_with(|| Candle {
+                open_time,
+                open: price.usd_price,
+                high: price.usd_price,
+                low: price.usd_price,
+                close: price.usd_price,
+                volume: price.amount,
+                source: "implied".to_string(),
+            });
+    }
+    buckets.into_values().collect()
+}
+
+/// Record the implied prices of a wallet's priced swaps within `window` in the shared book
+pub fn record_implied_prices(swaps: &[PricedSwap], window: (Option<u64>, u64), settings: &Settings) {
+    let prices = implied_prices(swaps, &settings.wallet_address, &settings.stablecoins);
+    let count = prices.len();
+    let book = ImpliedPriceBook::open(&get_implied_prices_path());
+    match book.record(&settings.wallet_address, window, prices) {
+        Ok(()) => println!("📈 Recorded {} implied prices", count),
+        Err(e) => println!("⚠️  Failed to save implied prices: {}", e),
+    }
+}
+
+/// Last-resort provider building candles from implied prices
+pub struct ImpliedPriceProvider {
+    book: Arc<ImpliedPriceBook>,
+    /// `None` pools the prices of all analyzed wallets
+    wallet: Option<String>,
+}
+
+impl ImpliedPriceProvider {
+    pub fn new(book: Arc<ImpliedPriceBook>, wallet: Option<String>) -> Self {
+        ImpliedPriceProvider { book, wallet }
+    }
+
+    /// Provider for `use_implied_prices`, pooled if `pool_implied_prices` is set
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        if !settings.config.use_implied_prices.unwrap_or(false) {
+            return None;
+        }
+        let pooled = settings.config.pool_implied_prices.unwrap_or(false);
+        let wallet = (!pooled).then(|| settings.wallet_address.clone());
+        Some(ImpliedPriceProvider::new(
+            ImpliedPriceBook::open(&get_implied_prices_path()),
+            wallet,
+        ))
+    }
+}
+
+#[async_trait]
+impl PriceProvider for ImpliedPriceProvider {
+    fn name(&self) -> &str {
+        "implied"
+    }
+
+    async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        let step = interval_secs(interval).ok_or_else(|| format!("Unknown interval {}", interval))?;
+        let end_ts = end_ts - end_ts % step + step - 1;
+        let prices = self.book.prices(mint, self.wallet.as_deref(), start_ts - start_ts % step, end_ts);
+        let candles = implied_candles(&prices, step);
+
+        if candles.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(candles))
+    }
+}
@@ -0,0 +1,134 @@
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::task::AbortHandle;
+use crate::modules::types::{JobStage, JobStatus};
+
+/// Finished jobs are dropped this long after their last update
+const JOB_RETENTION_SECS: u64 = 3600;
+
+struct Job {
+    status: JobStatus,
+    abort: Option<AbortHandle>,
+}
+
+/// Jobs of the process, shared by every request
+fn jobs() -> &'static Mutex<HashMap<String, Job>> {
+    static JOBS: OnceLock<Mutex<HashMap<String, Job>>> = OnceLock::new();
+    JOBS.get_or_init(Default::default)
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
+
+/// Handle the pipeline reports a job's progress through
+pub struct JobProgress {
+    id: String,
+}
+
+impl JobProgress {
+    /// Progress handle of an existing job
+    pub fn for_job(id: &str) -> Self {
+        JobProgress { id: id.to_string() }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Apply `f` to the job's status unless the job has already finished
+    pub fn update(&self, f: impl FnOnce(&mut JobStatus)) {
+        let mut jobs = jobs().lock().unwrap();
+        if let Some(job) = jobs.get_mut(&self.id) {
+            if !job.status.stage.is_finished() {
+                f(&mut job.status);
+                job.status.updated_at = now();
+            }
+        }
+    }
+
+    /// Remember the task running the job so it can be cancelled
+    pub fn set_abort_handle(&self, abort: AbortHandle) {
+        if let Some(job) = jobs().lock().unwrap().get_mut(&self.id) {
+            // Cancelled before the task was registered
+            if job.status.stage == JobStage::Cancelled {
+                abort.abort();
+            } else {
+                job.abort = Some(abort);
+            }
+        }
+    }
+
+    /// Record the job's result or error. Ignored if the job was cancelled meanwhile.
+    pub fn finish(&self, result: Result<Value, String>) {
+        self.update(|status| match result {
+            Ok(value) => {
+                status.stage = JobStage::Done;
+                status.result = Some(value);
+            }
+            Err(e) => {
+                status.stage = JobStage::Failed;
+                status.error = Some(e);
+            }
+        });
+    }
+}
+
+/// Register a queued job for `wallet_address`
+pub fn create_job(wallet_address: &str) -> JobProgress {
+    static NEXT_ID: Mutex<u64> = Mutex::new(0);
+
+    let created_at = now();
+    let id = {
+        let mut next_id = NEXT_ID.lock().unwrap();
+        *next_id += 1;
+        format!("{:x}-{}", created_at, next_id)
+    };
+
+    let mut jobs = jobs().lock().unwrap();
+    jobs.retain(|_, job| {
+        !job.status.stage.is_finished() || job.status.updated_at + JOB_RETENTION_SECS > created_at
+    });
+    jobs.insert(
+        id.clone(),
+        Job {
+            status: JobStatus {
+                id: id.clone(),
+                wallet_address: wallet_address.to_string(),
+                stage: JobStage::Queued,
+                transactions: None,
+                swaps: None,
+                priced_swaps: None,
+                unpriced_swaps: None,
+                receipts: None,
+                tokens: None,
+                created_at,
+                updated_at: created_at,
+                result: None,
+                error: None,
+            },
+            abort: None,
+        },
+    );
+    JobProgress { id }
+}
+
+pub fn job_status(id: &str) -> Option<JobStatus> {
+    jobs().lock().unwrap().get(id).map(|job| job.status.clone())
+}
+
+/// Abort a running job. Finished jobs are returned unchanged; `None` if there is no such job.
+pub fn cancel_job(id: &str) -> Option<JobStatus> {
+    let mut jobs = jobs().lock().unwrap();
+    let job = jobs.get_mut(id)?;
+    if !job.status.stage.is_finished() {
+        if let Some(abort) = job.abort.take() {
+            abort.abort();
+        }
+        job.status.stage = JobStage::Cancelled;
+        job.status.updated_at = now();
+    }
+    Some(job.status.clone())
+}
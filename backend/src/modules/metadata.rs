@@ -0,0 +1,258 @@
+use crate::modules::types::Settings;
+use base64::Engine;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const TOKEN_METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+/// `getMultipleAccounts` accepts at most 100 keys per call
+const RPC_BATCH_SIZE: usize = 100;
+/// Offset of the `decimals` byte in an SPL Token mint account
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+#[derive(Debug, Clone)]
+pub struct Creator {
+    pub address: String,
+    pub verified: bool,
+    pub share: u8,
+}
+
+/// Decoded Metaplex Token Metadata account
+#[derive(Debug, Clone)]
+pub struct TokenMetadata {
+    pub update_authority: String,
+    pub mint: String,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Vec<Creator>,
+}
+
+/// Derive the metadata PDA for a mint: seeds ["metadata", program_id, mint]
+pub fn find_metadata_pda(mint: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let program_id = decode_pubkey(TOKEN_METADATA_PROGRAM_ID)?;
+    let mint = decode_pubkey(mint)?;
+
+    for bump in (0..=u8::MAX).rev() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"metadata");
+        hasher.update(program_id);
+        hasher.update(mint);
+        hasher.update([bump]);
+        hasher.update(program_id);
+        hasher.update(b"ProgramDerivedAddress");
+        let hash: [u8; 32] = hasher.finalize().into();
+
+        // A valid PDA must lie off the ed25519 curve
+        if CompressedEdwardsY(hash).decompress().is_none() {
+            return Ok(bs58::encode(hash).into_string());
+        }
+    }
+
+    Err(format!("No viable bump seed for mint {}", bs58::encode(mint).into_string()).into())
+}
+
+fn decode_pubkey(address: &str) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+    let bytes = bs58::decode(address).into_vec()?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("Invalid public key: {}", address).into())
+}
+
+/// Minimal borsh reader over the raw account bytes
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        let end = self.pos + len;
+        let slice = self
+            .data
+            .get(self.pos..end)
+            .ok_or_else(|| format!("Metadata account truncated at byte {}", self.pos))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn std::error::Error>> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn pubkey(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(bs58::encode(self.take(32)?).into_string())
+    }
+
+    /// Borsh string; Metaplex pads fixed-width fields with null bytes
+    fn string(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let len = self.u32()? as usize;
+        let raw = String::from_utf8_lossy(self.take(len)?).into_owned();
+        Ok(raw.trim_matches(char::from(0)).trim().to_string())
+    }
+}
+
+/// Decode a Metaplex Token Metadata account (borsh layout, key = MetadataV1)
+pub fn decode_metadata(data: &[u8]) -> Result<TokenMetadata, Box<dyn std::error::Error>> {
+    let mut reader = Reader { data, pos: 0 };
+
+    let key = reader.u8()?;
+    if key != 4 {
+        return Err(format!("Not a MetadataV1 account (key = {})", key).into());
+    }
+
+    let update_authority = reader.pubkey()?;
+    let mint = reader.pubkey()?;
+    let name = reader.string()?;
+    let symbol = reader.string()?;
+    let uri = reader.string()?;
+    let seller_fee_basis_points = reader.u16()?;
+
+    let mut creators = vec![];
+    if reader.u8()? == 1 {
+        let count = reader.u32()?;
+        for _ in 0..count {
+            creators.push(Creator {
+                address: reader.pubkey()?,
+                verified: reader.u8()? == 1,
+                share: reader.u8()?,
+            });
+        }
+    }
+
+    Ok(TokenMetadata {
+        update_authority,
+        mint,
+        name,
+        symbol,
+        uri,
+        seller_fee_basis_points,
+        creators,
+    })
+}
+
+/// Decimals stored in an SPL Token (or Token-2022) mint account
+pub fn decode_mint_decimals(data: &[u8]) -> Option<u8> {
+    data.get(MINT_DECIMALS_OFFSET).copied()
+}
+
+/// Load account data from a `solana account <addr> --output json` snapshot in `dir`
+pub fn load_account_snapshot(dir: &str, address: &str) -> Option<Vec<u8>> {
+    let path = Path::new(dir).join(format!("{}.json", address));
+    let content = fs::read_to_string(path).ok()?;
+    let snapshot: Value = serde_json::from_str(&content).ok()?;
+    let encoded = snapshot
+        .get("account")
+        .and_then(|a| a.get("data"))
+        .and_then(|d| d.get(0))
+        .and_then(|d| d.as_str())?;
+    base64::engine::general_purpose::STANDARD.decode(encoded).ok()
+}
+
+/// Fetch raw account data via `getMultipleAccounts`, keyed by address
+pub async fn fetch_accounts_rpc(
+    client: &Client,
+    rpc_url: &str,
+    addresses: &[String],
+) -> Result<HashMap<String, Vec<u8>>, Box<dyn std::error::Error>> {
+    let mut accounts = HashMap::new();
+
+    for chunk in addresses.chunks(RPC_BATCH_SIZE) {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getMultipleAccounts",
+            "params": [chunk, { "encoding": "base64" }]
+        });
+        let resp: Value = client.post(rpc_url).json(&payload).send().await?.json().await?;
+
+        let values = resp
+            .get("result")
+            .and_then(|r| r.get("value"))
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| format!("Unexpected RPC response: {}", resp))?;
+
+        for (address, value) in chunk.iter().zip(values) {
+            let data = value
+                .get("data")
+                .and_then(|d| d.get(0))
+                .and_then(|d| d.as_str())
+                .and_then(|d| base64::engine::general_purpose::STANDARD.decode(d).ok());
+            if let Some(data) = data {
+                accounts.insert(address.clone(), data);
+            }
+        }
+    }
+
+    Ok(accounts)
+}
+
+/// Resolve names and decimals for `mints` from Metaplex metadata and mint accounts.
+/// Local snapshots are tried first; anything missing is fetched over RPC.
+pub async fn resolve_token_metadata(
+    mints: &[String],
+    settings: &Settings,
+) -> HashMap<String, (String, Option<u8>)> {
+    let snapshot_dir = settings.config.account_snapshot_dir.as_deref();
+    let rpc_url = settings.config.solana_rpc_url.as_deref().unwrap_or(DEFAULT_RPC_URL);
+
+    let mut pdas = HashMap::new();
+    for mint in mints {
+        match find_metadata_pda(mint) {
+            Ok(pda) => {
+                pdas.insert(mint.clone(), pda);
+            }
+            Err(e) => println!("⚠️  Could not derive metadata PDA for {}: {}", mint, e),
+        }
+    }
+
+    let mut accounts: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut missing = vec![];
+    for address in mints.iter().chain(pdas.values()) {
+        match snapshot_dir.and_then(|dir| load_account_snapshot(dir, address)) {
+            Some(data) => {
+                accounts.insert(address.clone(), data);
+            }
+            None => missing.push(address.clone()),
+        }
+    }
+
+    if !missing.is_empty() {
+        println!("Fetching {} metadata accounts via RPC...", missing.len());
+        match fetch_accounts_rpc(&Client::new(), rpc_url, &missing).await {
+            Ok(fetched) => accounts.extend(fetched),
+            Err(e) => println!("⚠️ Error calling Solana RPC: {}", e),
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    for (mint, pda) in &pdas {
+        let Some(data) = accounts.get(pda) else {
+            continue;
+        };
+        match decode_metadata(data) {
+            Ok(metadata) if !metadata.name.is_empty() => {
+                let decimals = accounts.get(mint).and_then(|d| decode_mint_decimals(d));
+                resolved.insert(mint.clone(), (metadata.name, decimals));
+            }
+            Ok(_) => {}
+            Err(e) => println!("⚠️  Could not decode metadata for {}: {}", mint, e),
+        }
+    }
+
+    resolved
+}
@@ -1,6 +1,21 @@
 pub mod utils;
+pub mod amounts;
 pub mod prices;
 pub mod types;
 pub mod transactions;
 pub mod swaps;
-pub mod pnl;
\ No newline at end of file
+pub mod pnl;
+pub mod stablecoins;
+pub mod metadata;
+pub mod providers;
+pub mod price_store;
+pub mod fx;
+pub mod implied_prices;
+pub mod execution;
+pub mod pnl_series;
+pub mod stats;
+pub mod receipts;
+pub mod tax;
+pub mod explain;
+pub mod resources;
+pub mod jobs;
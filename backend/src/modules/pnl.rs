@@ -1,160 +1,477 @@
-use crate::modules::types::{Settings, TokenPnl, PricedSwap, BuyPart, SellPart};
+use crate::modules::types::{Settings, TokenPnl, PricedSwap, BuyPart, SellPart, NamedSwap, PriceSampling, SamplingComparison, CostBasisMethod, MarkStatus, Receipt, Trade};
+use crate::modules::amounts::{to_decimal, TokenAmount};
+use crate::modules::fx::{apply_base_currency, base_currency, usd_rates_at};
+use crate::modules::prices::{last_prices, price_swaps};
+use crate::modules::stablecoins::StablecoinRegistry;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
 use std::io::Write;
 
-fn is_stable(token: &str) -> bool {
-    matches!(token, "USDC" | "USDT" | "USD" | "USDL" | "PAI" | "UXD")
+/// Running state for one token while walking through the swaps
+#[derive(Default)]
+struct TokenLedger {
+    token: String,
+    buys: Vec<BuyPart>,
+    open_lots: VecDeque<BuyPart>,
+    sells: Vec<SellPart>,
+    trades: Vec<Trade>,
+    realized_pnl: Decimal,
+    unpriced_signatures: Vec<String>,
+    unmatched_disposals: Vec<SellPart>,
+    unmatched_proceeds: Decimal,
 }
 
-pub fn calculate_direct_token_pnl(swaps: &[PricedSwap]) -> Vec<TokenPnl> {
-    use std::collections::{HashMap, VecDeque};
+/// Ledger for `mint`, created on first use. Ledgers opened by a receipt get
+/// their name from the first swap of the token.
+fn ledger<'a>(token_map: &'a mut HashMap<String, TokenLedger>, mint: &str, token: &str) -> &'a mut TokenLedger {
+    let entry = token_map.entry(mint.to_string()).or_default();
+    if entry.token.is_empty() {
+        entry.token = token.to_string();
+    }
+    entry
+}
+
+/// Open a lot for every receipt up to `until`, at the receipt's cost basis or zero
+fn receive_lots<'a>(
+    token_map: &mut HashMap<String, TokenLedger>,
+    receipts: &mut std::iter::Peekable<impl Iterator<Item = &'a Receipt>>,
+    until: u64,
+) {
+    while let Some(receipt) = receipts.next_if(|r| r.timestamp <= until) {
+        let entry = ledger(token_map, &receipt.mint, "");
+        if receipt.cost.is_none() {
+            entry.unpriced_signatures.push(receipt.signature.clone());
+        }
+        let lot = BuyPart {
+            timestamp: receipt.timestamp,
+            signature: receipt.signature.clone(),
+            amount: TokenAmount::of_leg(receipt.raw.as_ref(), receipt.amount, None),
+            cost: receipt.cost.unwrap_or_default(),
+            receipt: Some(receipt.kind),
+        };
+        entry.buys.push(lot.clone());
+        entry.open_lots.push_back(lot);
+    }
+}
+
+/// How `calculate_direct_token_pnl` values swaps and matches sells to buy lots
+pub struct PnlOptions<'a> {
+    pub stablecoins: &'a StablecoinRegistry,
+    pub currency: String,
+    pub method: CostBasisMethod,
+    /// Buy signatures to consume per sell signature, for `CostBasisMethod::SpecificLot`
+    pub lot_selections: Option<&'a HashMap<String, Vec<String>>>,
+}
+
+impl<'a> PnlOptions<'a> {
+    /// `cost_basis_method` from config, falling back to the `fifo` flag (false = LIFO)
+    pub fn from_settings(settings: &'a Settings) -> Self {
+        let method = settings.config.cost_basis_method.unwrap_or(
+            match settings.config.fifo {
+                Some(false) => CostBasisMethod::Lifo,
+                _ => CostBasisMethod::Fifo,
+            },
+        );
+        PnlOptions {
+            stablecoins: &settings.stablecoins,
+            currency: base_currency(settings),
+            method,
+            lot_selections: settings.config.lot_selections.as_ref(),
+        }
+    }
+}
+
+/// Index of the next lot to sell from under `method`
+fn next_lot(buys: &VecDeque<BuyPart>, method: CostBasisMethod, selection: Option<&Vec<String>>) -> Option<usize> {
+    if buys.is_empty() {
+        return None;
+    }
+    let unit_cost = |b: &BuyPart| b.cost.checked_div(b.amount.to_decimal()).unwrap_or_default();
+    match method {
+        CostBasisMethod::Lifo => Some(buys.len() - 1),
+        CostBasisMethod::Hifo => (0..buys.len()).max_by_key(|&i| unit_cost(&buys[i])),
+        CostBasisMethod::SpecificLot => selection
+            .and_then(|sigs| sigs.iter().find_map(|sig| buys.iter().position(|b| &b.signature == sig)))
+            .or(Some(0)),
+        CostBasisMethod::Fifo | CostBasisMethod::AverageCost => Some(0),
+    }
+}
+
+/// Decimal places value shares are rounded to, so that what is left of a lot's cost
+/// or a sell's proceeds after taking a share is exact
+const VALUE_SCALE: u32 = 12;
+
+/// Cost of `part` of a lot, exactly the lot's cost when the whole lot is used
+fn cost_share(lot: &BuyPart, part: TokenAmount) -> Decimal {
+    if part >= lot.amount {
+        return lot.cost;
+    }
+    (lot.cost * part.to_decimal() / lot.amount.to_decimal()).round_dp(VALUE_SCALE)
+}
+
+/// Remove up to `amount` from the open lots and return the consumed pieces,
+/// each with its share of the lot's cost
+fn consume_lots(
+    buys: &mut VecDeque<BuyPart>,
+    amount: TokenAmount,
+    method: CostBasisMethod,
+    selection: Option<&Vec<String>>,
+) -> Vec<BuyPart> {
+    let mut consumed = vec![];
+
+    if method == CostBasisMethod::AverageCost {
+        let total_amount: TokenAmount = buys.iter().map(|b| b.amount).sum();
+        if total_amount.is_zero() {
+            return consumed;
+        }
+        // Each lot gives up its share rounded down, then the rounding remainder is
+        // taken from the lots in order until the whole amount is used
+        let used = amount.min(total_amount);
+        let mut parts: Vec<TokenAmount> = buys.iter().map(|b| b.amount.mul_ratio(used, total_amount)).collect();
+        let mut remainder = used.saturating_sub(parts.iter().copied().sum());
+        for (part, buy) in parts.iter_mut().zip(buys.iter()) {
+            if remainder.is_zero() {
+                break;
+            }
+            let extra = remainder.min(buy.amount.saturating_sub(*part));
+            *part += extra;
+            remainder = remainder.saturating_sub(extra);
+        }
+
+        for (buy, part) in buys.iter_mut().zip(parts) {
+            if part.is_zero() {
+                continue;
+            }
+            let cost = cost_share(buy, part);
+            consumed.push(BuyPart {
+                amount: part,
+                cost,
+                ..buy.clone()
+            });
+            buy.amount = buy.amount.saturating_sub(part);
+            buy.cost -= cost;
+        }
+        buys.retain(|b| !b.amount.is_zero());
+        return consumed;
+    }
+
+    let mut remaining = amount;
+    while !remaining.is_zero() {
+        let Some(index) = next_lot(buys, method, selection) else {
+            break;
+        };
+        let buy = &mut buys[index];
+        let used = remaining.min(buy.amount);
+        let cost = cost_share(buy, used);
+        consumed.push(BuyPart {
+            amount: used,
+            cost,
+            ..buy.clone()
+        });
+        remaining = remaining.saturating_sub(used);
+        buy.amount = buy.amount.saturating_sub(used);
+        buy.cost -= cost;
+        if buy.amount.is_zero() {
+            buys.remove(index);
+        }
+    }
+    consumed
+}
+
+/// One `Trade` per consumed lot, splitting the sell proceeds by amount. The part of the
+/// sell no lot covered gets no trade; its share of the proceeds is left unallocated.
+fn matched_trades(
+    swap: &PricedSwap,
+    mint: &str,
+    token: &str,
+    amount: TokenAmount,
+    proceeds: Decimal,
+    consumed: &[BuyPart],
+) -> Vec<Trade> {
+    let share = |part: TokenAmount| {
+        if amount.is_zero() {
+            Decimal::ZERO
+        } else {
+            (proceeds * part.to_decimal() / amount.to_decimal()).round_dp(VALUE_SCALE)
+        }
+    };
+    let matched: TokenAmount = consumed.iter().map(|lot| lot.amount).sum();
+    let unmatched = amount.saturating_sub(matched);
+
+    let mut allocated = Decimal::ZERO;
+    consumed
+        .iter()
+        .enumerate()
+        .map(|(i, lot)| {
+            // With nothing unmatched the last lot takes the rounding remainder
+            let lot_proceeds = if unmatched.is_zero() && i == consumed.len() - 1 {
+                proceeds - allocated
+            } else {
+                share(lot.amount)
+            };
+            allocated += lot_proceeds;
+            Trade {
+                token_mint: mint.to_string(),
+                token_name: token.to_string(),
+                buy_signature: Some(lot.signature.clone()),
+                sell_signature: swap.signature.clone(),
+                buy_timestamp: Some(lot.timestamp),
+                sell_timestamp: swap.timestamp,
+                amount: lot.amount,
+                cost: lot.cost,
+                proceeds: lot_proceeds,
+                pnl: lot_proceeds - lot.cost,
+                holding_period_secs: Some(swap.timestamp.saturating_sub(lot.timestamp)),
+            }
+        })
+        .collect()
+}
 
-    let mut token_map: HashMap<String, (VecDeque<BuyPart>, Vec<SellPart>, f64)> = HashMap::new();
+/// PnL per token with all values in `options.currency`, taken from each swap's `base_value`.
+/// Receipts (sorted by time) open lots at their `cost` alongside the bought legs of swaps.
+pub fn calculate_direct_token_pnl(swaps: &[PricedSwap], receipts: &[Receipt], options: &PnlOptions) -> Vec<TokenPnl> {
+    let is_stable = |mint: &str| options.stablecoins.is_stable(mint);
+    let mut token_map: HashMap<String, TokenLedger> = HashMap::new();
+    let mut receipts = receipts.iter().filter(|r| !is_stable(&r.mint)).peekable();
 
     for swap in swaps {
-        if swap.usd_value.is_none() {
+        receive_lots(&mut token_map, &mut receipts, swap.timestamp);
+        if swap.sold_mint == swap.bought_mint {
             continue;
         }
-        let usd_value = swap.usd_value.unwrap();
-
-        // === Grouping: determine which token to attribute PnL to ===
-        let group_token = match (
-            swap.sold_token_name.as_str(),
-            swap.bought_token_name.as_str(),
-        ) {
-            // Ignore swaps between stables or WSOL
-            (a, b) if is_stable(a) && is_stable(b) => continue,
-            (a, b) if a == "Wrapped SOL" && b == "Wrapped SOL" => continue,
-
-            // If buying a token with WSOL, group by the bought token
-            ("Wrapped SOL", other) if !is_stable(other) => Some(other.to_string()),
-            // If selling a token into WSOL, group by the sold token
-            (other, "Wrapped SOL") if !is_stable(other) => Some(other.to_string()),
-
-            // Skip swaps like WSOL <-> stable
-            ("Wrapped SOL", other) if is_stable(other) => continue,
-            (other, "Wrapped SOL") if is_stable(other) => continue,
-
-            // Fallback: group by sold token
-            _ => Some(swap.sold_token_name.clone()),
+        // Every swap disposes of the sold asset and acquires the bought one at the same value.
+        // Stablecoins are cash and have no lots; SOL is tracked like any other token.
+        let sold = (!is_stable(&swap.sold_mint)).then_some((&swap.sold_mint, &swap.sold_token_name));
+        let bought = (!is_stable(&swap.bought_mint)).then_some((&swap.bought_mint, &swap.bought_token_name));
+
+        let Some(value) = swap.base_value.map(to_decimal) else {
+            // Keep unpriced swaps visible instead of silently dropping them
+            for (mint, token) in sold.into_iter().chain(bought) {
+                ledger(&mut token_map, mint, token).unpriced_signatures.push(swap.signature.clone());
+            }
+            continue;
         };
 
-        if let Some(token) = group_token {
-            let entry = token_map
-                .entry(token.clone())
-                .or_insert_with(|| (VecDeque::new(), Vec::new(), 0.0));
+        if let Some((mint, token)) = sold {
+            let entry = ledger(&mut token_map, mint, token);
+            let selection = options.lot_selections.and_then(|l| l.get(&swap.signature));
+            let amount = TokenAmount::of_leg(swap.sold_raw.as_ref(), swap.sold_amount, swap.sold_decimals);
+            let consumed = consume_lots(&mut entry.open_lots, amount, options.method, selection);
+            let cost_basis: Decimal = consumed.iter().map(|lot| lot.cost).sum();
+            let trades = matched_trades(swap, mint, token, amount, value, &consumed);
+            let matched_proceeds: Decimal = trades.iter().map(|t| t.proceeds).sum();
 
-            // Track buys for all non-stable tokens (including Wrapped SOL if bought)
-            if !is_stable(&swap.bought_token_name) {
-                entry.0.push_back(BuyPart {
+            // Proceeds of the part no lot covered have no known cost and stay out of realized_pnl
+            let unmatched = amount.saturating_sub(consumed.iter().map(|lot| lot.amount).sum());
+            if !unmatched.is_zero() {
+                entry.unmatched_disposals.push(SellPart {
                     timestamp: swap.timestamp,
-                    amount: swap.bought_amount,
-                    cost_usd: usd_value,
+                    signature: swap.signature.clone(),
+                    amount: unmatched,
+                    proceeds: value - matched_proceeds,
                 });
+                entry.unmatched_proceeds += value - matched_proceeds;
             }
+            entry.trades.extend(trades);
 
-            // SELL logic
-            let mut remaining = swap.sold_amount;
-            let mut cost_basis = 0.0;
-
-            while remaining > 0.0 {
-                if let Some(mut buy) = entry.0.pop_front() {
-                    let used = remaining.min(buy.amount);
-                    let ratio = used / buy.amount;
-                    cost_basis += buy.cost_usd * ratio;
-                    remaining -= used;
-                    buy.amount -= used;
-                    if buy.amount > 0.0 {
-                        entry.0.push_front(buy);
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
-
-            let sold_amount = swap.sold_amount - remaining;
-
-            entry.1.push(SellPart {
+            entry.sells.push(SellPart {
                 timestamp: swap.timestamp,
-                amount: sold_amount,
-                proceeds_usd: usd_value,
+                signature: swap.signature.clone(),
+                amount,
+                proceeds: value,
             });
+            entry.realized_pnl += matched_proceeds - cost_basis;
+        }
 
-            entry.2 += usd_value - cost_basis;
+        if let Some((mint, token)) = bought {
+            let entry = ledger(&mut token_map, mint, token);
+            let buy = BuyPart {
+                timestamp: swap.timestamp,
+                signature: swap.signature.clone(),
+                amount: TokenAmount::of_leg(swap.bought_raw.as_ref(), swap.bought_amount, swap.bought_decimals),
+                cost: value,
+                receipt: None,
+            };
+            entry.buys.push(buy.clone());
+            entry.open_lots.push_back(buy);
         }
     }
 
-    token_map
-        .into_iter()
-        .map(|(token, (buys, sells, realized_pnl))| {
-            if token == "Lamine Yamal" {
-                println!("--- DEBUG: Lamine Yamal PnL Breakdown ---");
-                println!("Total Bought: {:.2}, Total Cost: {:.2}", 
-                        buys.iter().map(|b| b.amount).sum::<f64>(), 
-                        buys.iter().map(|b| b.cost_usd).sum::<f64>());
-                for (i, b) in buys.iter().enumerate() {
-                    println!("Buy #{:>2}: {:.4} tokens for ${:.4} at {}", i+1, b.amount, b.cost_usd, b.timestamp);
-                }
-
-                println!("Total Sold: {:.2}", sells.iter().map(|s| s.amount).sum::<f64>());
-                for (i, s) in sells.iter().enumerate() {
-                    println!("Sell #{:>2}: {:.4} tokens for ${:.4} at {}", i+1, s.amount, s.proceeds_usd, s.timestamp);
-                }
-
-                println!("Realized PnL: {:.2}\n", realized_pnl);
-            }
+    receive_lots(&mut token_map, &mut receipts, u64::MAX);
 
+    let unmatched = token_map.values().filter(|l| !l.unmatched_disposals.is_empty()).count();
+    if unmatched > 0 {
+        println!("⚠️  {} tokens were sold beyond their lots; see unmatched_disposals", unmatched);
+    }
 
-            let total_bought: f64 = buys.iter().map(|b| b.amount).sum();
-            let total_cost: f64 = buys.iter().map(|b| b.cost_usd).sum();
-            let average_cost = if total_bought > 0.0 {
-                total_cost / total_bought
-            } else {
-                0.0
-            };
-            let total_sold: f64 = sells.iter().map(|s| s.amount).sum();
+    token_map
+        .into_iter()
+        .map(|(mint, TokenLedger { token, buys, open_lots, sells, trades, realized_pnl, unpriced_signatures, unmatched_disposals, unmatched_proceeds })| {
+            let token = if token.is_empty() { "UNKNOWN".to_string() } else { token };
+            let total_bought: TokenAmount = buys.iter().map(|b| b.amount).sum();
+            let remaining_amount: TokenAmount = open_lots.iter().map(|b| b.amount).sum();
+            let open_cost: Decimal = open_lots.iter().map(|b| b.cost).sum();
+            let average_cost = open_cost
+                .checked_div(remaining_amount.to_decimal())
+                .unwrap_or_default();
+            let total_sold: TokenAmount = sells.iter().map(|s| s.amount).sum();
 
             TokenPnl {
                 token,
-                buys: buys.into(),
+                mint,
+                currency: options.currency.clone(),
+                buys,
                 sells,
+                open_lots: open_lots.into(),
+                trades,
                 realized_pnl,
                 total_bought,
                 total_sold,
-                remaining_amount: total_bought,
-                average_cost_usd: average_cost,
+                remaining_amount,
+                average_cost,
+                open_cost,
+                mark_price: None,
+                mark_timestamp: None,
+                current_value: None,
+                unrealized_pnl: None,
+                total_pnl: None,
+                mark_status: MarkStatus::Closed,
+                unpriced_signatures,
+                unmatched_disposals,
+                unmatched_proceeds,
+                cost_basis_method: options.method,
             }
         })
         .collect()
 }
 
-pub async fn calc_pnl(
-    priced_swaps: &[PricedSwap],
-    settings: &Settings,
-) -> Result<Vec<TokenPnl>, Box<dyn std::error::Error>> {
-    let write_cache_files = settings.config.write_cache_files.unwrap_or(false);
+/// Time open positions are valued at: `as_of` from config, or now
+pub fn valuation_time(settings: &Settings) -> u64 {
+    settings
+        .config
+        .as_of
+        .unwrap_or_else(|| chrono::Utc::now().timestamp() as u64)
+}
 
-    let mut swaps_sorted = priced_swaps.to_vec();
+/// Value the open lots of every token at its last price at or before `as_of`,
+/// converted to the token's currency at that day's rate. Tokens with open lots but
+/// no price are flagged `NoPrice`, or `NoFxRate` if only the rate is missing, and keep
+/// `None` values instead of zero.
+pub async fn mark_to_market(trades: &mut [TokenPnl], as_of: u64, settings: &Settings) {
+    let open: Vec<usize> = (0..trades.len())
+        .filter(|&i| !trades[i].remaining_amount.is_zero())
+        .collect();
+    let mints: Vec<String> = open.iter().map(|&i| trades[i].mint.clone()).collect();
+    let prices = last_prices(&mints, as_of, settings).await;
+    let currency = base_currency(settings);
+    let rate = usd_rates_at(&currency, &[as_of], settings).await.first().copied().flatten();
+
+    for trade in trades.iter_mut() {
+        trade.mark_status = MarkStatus::Closed;
+        trade.current_value = Some(Decimal::ZERO);
+        trade.unrealized_pnl = Some(Decimal::ZERO);
+        trade.total_pnl = Some(trade.realized_pnl);
+    }
 
-    for swap in &swaps_sorted {
-        if swap.sold_token_name.contains("Yamal") || swap.bought_token_name.contains("Yamal") {
-            println!(
-                "🧪 DEBUG normalized: {} -> sold: {} (dec: {:?}) bought: {} (dec: {:?})",
-                swap.signature,
-                swap.sold_amount, swap.sold_decimals,
-                swap.bought_amount, swap.bought_decimals
-            );
+    for (i, price) in open.into_iter().zip(prices) {
+        let trade = &mut trades[i];
+        match (price, rate) {
+            (Some((usd_price, timestamp)), Some(rate)) => {
+                let mark_price = to_decimal(usd_price * rate);
+                let current_value = trade.remaining_amount.to_decimal() * mark_price;
+                let unrealized_pnl = current_value - trade.open_cost;
+                trade.mark_status = MarkStatus::Priced;
+                trade.mark_price = Some(mark_price);
+                trade.mark_timestamp = Some(timestamp);
+                trade.current_value = Some(current_value);
+                trade.unrealized_pnl = Some(unrealized_pnl);
+                trade.total_pnl = Some(trade.realized_pnl + unrealized_pnl);
+            }
+            (price, rate) => {
+                // A missing FX rate must not make a priced token look dead
+                trade.mark_status = match (price, rate) {
+                    (Some(_), None) => MarkStatus::NoFxRate,
+                    _ => MarkStatus::NoPrice,
+                };
+                trade.current_value = None;
+                trade.unrealized_pnl = None;
+                trade.total_pnl = None;
+            }
         }
     }
 
+    let dead = trades.iter().filter(|t| t.mark_status == MarkStatus::NoPrice).count();
+    if dead > 0 {
+        println!("⚠️  {} open positions have no price and are flagged", dead);
+    }
+    let no_fx = trades.iter().filter(|t| t.mark_status == MarkStatus::NoFxRate).count();
+    if no_fx > 0 {
+        println!("⚠️  {} open positions have no {} rate at {} and are flagged", no_fx, currency, as_of);
+    }
+}
+
+/// Swaps in processing order: by timestamp, then signature
+fn sorted_swaps(priced_swaps: &[PricedSwap]) -> Vec<PricedSwap> {
+    let mut swaps_sorted = priced_swaps.to_vec();
     swaps_sorted.sort_by(|a, b| {
         a.timestamp
             .cmp(&b.timestamp)
             .then(a.signature.cmp(&b.signature))
     });
+    swaps_sorted
+}
+
+/// Realized PnL of the wallet under every price sampling mode,
+/// with the difference to the configured `price_sampling`
+pub async fn compare_price_sampling(
+    named_swaps: &[NamedSwap],
+    receipts: &[Receipt],
+    settings: &Settings,
+) -> Vec<SamplingComparison> {
+    let configured = settings.config.price_sampling.unwrap_or_default();
+    let options = PnlOptions::from_settings(settings);
+    let mut comparisons = vec![];
+
+    for sampling in PriceSampling::ALL {
+        let mut priced = sorted_swaps(&price_swaps(named_swaps, settings, sampling).await);
+        apply_base_currency(&mut priced, settings).await;
+        let realized_pnl: Decimal = calculate_direct_token_pnl(&priced, receipts, &options)
+            .iter()
+            .map(|t| t.realized_pnl)
+            .sum();
+        comparisons.push(SamplingComparison {
+            sampling,
+            realized_pnl,
+            delta: Decimal::ZERO,
+            unpriced_swaps: priced.iter().filter(|s| s.base_value.is_none()).count(),
+        });
+    }
+
+    let baseline = comparisons
+        .iter()
+        .find(|c| c.sampling == configured)
+        .map(|c| c.realized_pnl)
+        .unwrap_or_default();
+    for comparison in comparisons.iter_mut() {
+        comparison.delta = comparison.realized_pnl - baseline;
+    }
+
+    comparisons
+}
+
+pub async fn calc_pnl(
+    priced_swaps: &[PricedSwap],
+    receipts: &[Receipt],
+    settings: &Settings,
+) -> Result<Vec<TokenPnl>, Box<dyn std::error::Error>> {
+    let write_cache_files = settings.config.write_cache_files.unwrap_or(false);
+
+    let mut swaps_sorted = sorted_swaps(priced_swaps);
+    apply_base_currency(&mut swaps_sorted, settings).await;
 
-    let trades = calculate_direct_token_pnl(&swaps_sorted);
+    let mut trades = calculate_direct_token_pnl(&swaps_sorted, receipts, &PnlOptions::from_settings(settings));
+    mark_to_market(&mut trades, valuation_time(settings), settings).await;
 
     if write_cache_files {
         let out_path = format!("cache/trades_{}.json", settings.wallet_address);
@@ -168,3 +485,157 @@ pub async fn calc_pnl(
 
     Ok(trades)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::Config;
+    use serde_json::json;
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const TOKEN: &str = "TokenMint1111111111111111111111111111111111";
+
+    fn lot(signature: &str, raw: u128, decimals: u8, cost: i64) -> BuyPart {
+        BuyPart {
+            timestamp: 0,
+            signature: signature.to_string(),
+            amount: TokenAmount::new(raw, decimals),
+            cost: Decimal::from(cost),
+            receipt: None,
+        }
+    }
+
+    fn swap(signature: &str, timestamp: u64, sold: (&str, u128), bought: (&str, u128), value: f64) -> PricedSwap {
+        let leg = |mint: &str, raw: u128| {
+            let decimals = if mint == USDC { 6 } else { 9 };
+            json!({ "tokenAmount": raw.to_string(), "decimals": decimals })
+        };
+        serde_json::from_value(json!({
+            "timestamp": timestamp,
+            "signature": signature,
+            "sold_mint": sold.0,
+            "sold_token_name": sold.0,
+            "sold_amount": 0.0,
+            "sold_decimals": null,
+            "bought_mint": bought.0,
+            "bought_token_name": bought.0,
+            "bought_amount": 0.0,
+            "bought_decimals": null,
+            "sold_raw": leg(sold.0, sold.1),
+            "bought_raw": leg(bought.0, bought.1),
+            "pricing_method": "test",
+            "binance_sol_usd_price": null,
+            "usd_value": value,
+            "base_value": value,
+        }))
+        .unwrap()
+    }
+
+    fn stablecoins() -> StablecoinRegistry {
+        let config: Config = serde_json::from_value(json!({})).unwrap();
+        StablecoinRegistry::from_config(&config)
+    }
+
+    #[test]
+    fn average_cost_consumes_lots_without_dust() {
+        let mut lots: VecDeque<BuyPart> = VecDeque::from([
+            lot("a", 1_000_001, 6, 10),
+            lot("b", 333_333_333, 9, 7),
+            lot("c", 2, 0, 5),
+        ]);
+        let total: TokenAmount = lots.iter().map(|l| l.amount).sum();
+        let sold = TokenAmount::new(1_234_567_891, 9);
+
+        let consumed = consume_lots(&mut lots, sold, CostBasisMethod::AverageCost, None);
+        let taken: TokenAmount = consumed.iter().map(|l| l.amount).sum();
+        let left: TokenAmount = lots.iter().map(|l| l.amount).sum();
+        assert_eq!(taken, sold);
+        assert_eq!(taken + left, total);
+
+        let cost: Decimal = consumed.iter().chain(&lots).map(|l| l.cost).sum();
+        assert_eq!(cost, Decimal::from(22));
+
+        // Selling the rest empties every lot exactly
+        let consumed = consume_lots(&mut lots, left, CostBasisMethod::AverageCost, None);
+        assert!(lots.is_empty());
+        assert_eq!(consumed.iter().map(|l| l.amount).sum::<TokenAmount>(), left);
+    }
+
+    #[test]
+    fn average_cost_hands_out_a_remainder_larger_than_the_last_lot() {
+        let mut lots: VecDeque<BuyPart> = VecDeque::from([lot("a", 3, 0, 3), lot("b", 3, 0, 6), lot("c", 1, 0, 1)]);
+        let consumed = consume_lots(&mut lots, TokenAmount::new(6, 0), CostBasisMethod::AverageCost, None);
+
+        assert_eq!(consumed.iter().map(|l| l.amount).sum::<TokenAmount>(), TokenAmount::new(6, 0));
+        assert_eq!(lots.iter().map(|l| l.amount).sum::<TokenAmount>(), TokenAmount::new(1, 0));
+        let cost: Decimal = consumed.iter().chain(&lots).map(|l| l.cost).sum();
+        assert_eq!(cost, Decimal::from(10));
+    }
+
+    #[test]
+    fn fifo_consumes_mixed_decimal_lots_exactly() {
+        let mut lots: VecDeque<BuyPart> = VecDeque::from([lot("a", 1_000_000, 6, 3), lot("b", 500_000_000, 9, 2)]);
+        let consumed = consume_lots(&mut lots, TokenAmount::new(1_500_000_000, 9), CostBasisMethod::Fifo, None);
+        assert!(lots.is_empty());
+        assert_eq!(consumed.iter().map(|l| l.cost).sum::<Decimal>(), Decimal::from(5));
+    }
+
+    #[test]
+    fn trade_proceeds_sum_to_the_sell_value() {
+        let sell = swap("sell", 10, (TOKEN, 3), (USDC, 100_000_000), 100.0);
+        let consumed = vec![lot("a", 1, 0, 10), lot("b", 1, 0, 20), lot("c", 1, 0, 30)];
+        let trades = matched_trades(&sell, TOKEN, TOKEN, TokenAmount::new(3, 0), Decimal::from(100), &consumed);
+
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades.iter().map(|t| t.proceeds).sum::<Decimal>(), Decimal::from(100));
+        assert_eq!(trades.iter().map(|t| t.pnl).sum::<Decimal>(), Decimal::from(40));
+    }
+
+    #[test]
+    fn selling_everything_leaves_no_position() {
+        let swaps = vec![
+            swap("buy1", 1, (USDC, 10_000_000), (TOKEN, 1_000_000_001), 10.0),
+            swap("buy2", 2, (USDC, 20_000_000), (TOKEN, 2_333_333_333), 20.0),
+            swap("buy3", 3, (USDC, 30_000_000), (TOKEN, 7), 30.0),
+            swap("sell", 4, (TOKEN, 3_333_333_341), (USDC, 100_000_000), 100.0),
+        ];
+        let registry = stablecoins();
+        for method in [CostBasisMethod::Fifo, CostBasisMethod::Hifo, CostBasisMethod::AverageCost] {
+            let options = PnlOptions {
+                stablecoins: &registry,
+                currency: "USD".to_string(),
+                method,
+                lot_selections: None,
+            };
+            let pnls = calculate_direct_token_pnl(&swaps, &[], &options);
+            let token = pnls.iter().find(|t| t.mint == TOKEN).unwrap();
+
+            assert!(token.remaining_amount.is_zero(), "{:?}", method);
+            assert!(token.open_lots.is_empty(), "{:?}", method);
+            assert!(token.unmatched_disposals.is_empty(), "{:?}", method);
+            assert_eq!(token.trades.iter().map(|t| t.proceeds).sum::<Decimal>(), Decimal::from(100));
+            assert_eq!(token.realized_pnl, Decimal::from(40));
+        }
+    }
+
+    #[test]
+    fn unmatched_proceeds_stay_out_of_realized_pnl() {
+        let swaps = vec![
+            swap("buy", 1, (USDC, 10_000_000), (TOKEN, 1_000_000_000), 10.0),
+            swap("sell", 2, (TOKEN, 2_000_000_000), (USDC, 30_000_000), 30.0),
+        ];
+        let registry = stablecoins();
+        let options = PnlOptions {
+            stablecoins: &registry,
+            currency: "USD".to_string(),
+            method: CostBasisMethod::Fifo,
+            lot_selections: None,
+        };
+        let pnls = calculate_direct_token_pnl(&swaps, &[], &options);
+        let token = pnls.iter().find(|t| t.mint == TOKEN).unwrap();
+
+        assert_eq!(token.realized_pnl, Decimal::from(5));
+        assert_eq!(token.unmatched_proceeds, Decimal::from(15));
+        assert_eq!(token.unmatched_disposals[0].amount, TokenAmount::new(1_000_000_000, 9));
+    }
+}
@@ -0,0 +1,177 @@
+use chrono::{Datelike, Days, Months, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use crate::modules::fx::{base_currency, usd_rates_at};
+use crate::modules::amounts::{to_decimal, TokenAmount};
+use crate::modules::prices::last_prices_at;
+use crate::modules::types::{BucketSize, EquityPoint, PnlBucket, PnlSeries, Settings, TokenPnl};
+
+/// Parse an IANA timezone name, UTC if `None`
+pub fn parse_timezone(name: Option<&str>) -> Result<Tz, String> {
+    match name {
+        None => Ok(Tz::UTC),
+        Some(name) => name
+            .parse::<Tz>()
+            .map_err(|_| format!("Unknown timezone {}", name)),
+    }
+}
+
+fn local_date(ts: u64, tz: Tz) -> NaiveDate {
+    tz.timestamp_opt(ts as i64, 0)
+        .single()
+        .map(|dt| dt.date_naive())
+        .unwrap_or_default()
+}
+
+/// Unix timestamp of local midnight, or of 01:00 where a DST change skips midnight
+fn local_midnight(date: NaiveDate, tz: Tz) -> u64 {
+    [0, 1]
+        .iter()
+        .find_map(|&hour| tz.from_local_datetime(&date.and_hms_opt(hour, 0, 0)?).earliest())
+        .map(|dt| dt.timestamp() as u64)
+        .unwrap_or_default()
+}
+
+fn bucket_start(date: NaiveDate, bucket: BucketSize) -> NaiveDate {
+    match bucket {
+        BucketSize::Day => date,
+        BucketSize::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+        BucketSize::Month => date.with_day(1).unwrap_or(date),
+    }
+}
+
+fn next_bucket_start(start: NaiveDate, bucket: BucketSize) -> NaiveDate {
+    match bucket {
+        BucketSize::Day => start + Days::new(1),
+        BucketSize::Week => start + Days::new(7),
+        BucketSize::Month => start + Months::new(1),
+    }
+}
+
+/// Amount and remaining cost of the lots of `token` still open at `ts`
+fn open_position(token: &TokenPnl, ts: u64) -> (TokenAmount, Decimal) {
+    let bought: Vec<_> = token.buys.iter().filter(|b| b.timestamp <= ts).collect();
+    let matched: Vec<_> = token
+        .trades
+        .iter()
+        .filter(|t| t.buy_signature.is_some() && t.sell_timestamp <= ts)
+        .collect();
+
+    let amount = bought
+        .iter()
+        .map(|b| b.amount)
+        .sum::<TokenAmount>()
+        .saturating_sub(matched.iter().map(|t| t.amount).sum());
+    let cost = bought.iter().map(|b| b.cost).sum::<Decimal>() - matched.iter().map(|t| t.cost).sum::<Decimal>();
+    (amount, cost)
+}
+
+/// Realized PnL per bucket from the matched trades, open positions valued at each bucket end
+/// (or `as_of` for the current bucket), and the cumulative equity curve.
+/// Bucket boundaries are local midnights in `tz`.
+pub async fn pnl_series(
+    token_pnls: &[TokenPnl],
+    bucket: BucketSize,
+    tz: Tz,
+    as_of: u64,
+    settings: &Settings,
+) -> PnlSeries {
+    let currency = token_pnls
+        .first()
+        .map(|t| t.currency.clone())
+        .unwrap_or_else(|| base_currency(settings));
+    let mut series = PnlSeries {
+        currency: currency.clone(),
+        bucket,
+        timezone: tz.name().to_string(),
+        buckets: vec![],
+        equity_curve: vec![],
+    };
+
+    let first_ts = token_pnls
+        .iter()
+        .flat_map(|t| {
+            t.buys
+                .iter()
+                .map(|b| b.timestamp)
+                .chain(t.trades.iter().map(|tr| tr.sell_timestamp))
+        })
+        .min();
+    let Some(first_ts) = first_ts else {
+        return series;
+    };
+
+    let last_date = local_date(as_of, tz);
+    let mut start = bucket_start(local_date(first_ts, tz), bucket);
+    while start <= last_date {
+        let next = next_bucket_start(start, bucket);
+        series.buckets.push(PnlBucket {
+            label: start.format("%Y-%m-%d").to_string(),
+            start_ts: local_midnight(start, tz),
+            end_ts: local_midnight(next, tz) - 1,
+            realized_pnl: Decimal::ZERO,
+            trades: 0,
+            unrealized_pnl: Decimal::ZERO,
+            unpriced_positions: 0,
+            fx_rate_missing: false,
+        });
+        start = next;
+    }
+
+    for trade in token_pnls.iter().flat_map(|t| &t.trades) {
+        let index = series
+            .buckets
+            .partition_point(|b| b.start_ts <= trade.sell_timestamp)
+            .saturating_sub(1);
+        if let Some(b) = series.buckets.get_mut(index) {
+            b.realized_pnl += trade.pnl;
+            b.trades += 1;
+        }
+    }
+
+    let marks: Vec<u64> = series.buckets.iter().map(|b| b.end_ts.min(as_of)).collect();
+    let rates = usd_rates_at(&currency, &marks, settings).await;
+
+    // Open position of every token at every mark; each token ever held is priced once
+    let positions: Vec<Vec<(TokenAmount, Decimal)>> = token_pnls
+        .iter()
+        .map(|t| marks.iter().map(|&mark| open_position(t, mark)).collect())
+        .collect();
+    let held: Vec<usize> = (0..token_pnls.len())
+        .filter(|&i| positions[i].iter().any(|(amount, _)| !amount.is_zero()))
+        .collect();
+    let mints: Vec<String> = held.iter().map(|&i| token_pnls[i].mint.clone()).collect();
+    let prices = last_prices_at(&mints, &marks, settings).await;
+
+    for (index, (b, rate)) in series.buckets.iter_mut().zip(rates).enumerate() {
+        let open = held
+            .iter()
+            .zip(&prices)
+            .map(|(&i, prices)| (positions[i][index], prices[index]))
+            .filter(|((amount, _), _)| !amount.is_zero());
+        b.fx_rate_missing = rate.is_none() && open.clone().next().is_some();
+
+        for ((amount, cost), price) in open {
+            match (price, rate) {
+                (Some((usd_price, _)), Some(rate)) => {
+                    b.unrealized_pnl += amount.to_decimal() * to_decimal(usd_price * rate) - cost
+                }
+                (Some(_), None) => {}
+                (None, _) => b.unpriced_positions += 1,
+            }
+        }
+    }
+
+    let mut cumulative_realized = Decimal::ZERO;
+    for (b, &mark) in series.buckets.iter().zip(&marks) {
+        cumulative_realized += b.realized_pnl;
+        series.equity_curve.push(EquityPoint {
+            timestamp: mark,
+            cumulative_realized,
+            unrealized_pnl: b.unrealized_pnl,
+            total_pnl: cumulative_realized + b.unrealized_pnl,
+        });
+    }
+
+    series
+}
@@ -0,0 +1,378 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tokio::sync::watch;
+use crate::modules::providers::{interval_secs, PriceSeries};
+use crate::modules::types::Candle;
+
+pub const DEFAULT_PRICE_STORE_DIR: &str = "cache/prices";
+
+/// Candles for one asset and interval plus the time ranges already fetched,
+/// so ranges where a provider had no trades are not asked for again
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredSeries {
+    candles: BTreeMap<u64, Candle>,
+    covered: Vec<(u64, u64)>,
+}
+
+impl StoredSeries {
+    /// Sub-ranges of `[start_ts, end_ts]` not yet covered
+    fn gaps(&self, start_ts: u64, end_ts: u64) -> Vec<(u64, u64)> {
+        let mut gaps = vec![];
+        let mut cursor = start_ts;
+        for &(from, to) in &self.covered {
+            if to < cursor {
+                continue;
+            }
+            if from > end_ts {
+                break;
+            }
+            if from > cursor {
+                gaps.push((cursor, from - 1));
+            }
+            cursor = cursor.max(to.saturating_add(1));
+            if cursor > end_ts {
+                return gaps;
+            }
+        }
+        if cursor <= end_ts {
+            gaps.push((cursor, end_ts));
+        }
+        gaps
+    }
+
+    fn mark_covered(&mut self, start_ts: u64, end_ts: u64) {
+        self.covered.push((start_ts, end_ts));
+        self.covered.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = vec![];
+        for (from, to) in self.covered.drain(..) {
+            match merged.last_mut() {
+                Some(last) if from <= last.1.saturating_add(1) => last.1 = last.1.max(to),
+                _ => merged.push((from, to)),
+            }
+        }
+        self.covered = merged;
+    }
+}
+
+/// Hit/miss counters for one pricing run
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct StoreStats {
+    /// Lookups answered entirely from the store
+    pub hits: usize,
+    /// Lookups that had to ask a provider for at least one gap
+    pub misses: usize,
+    /// Lookups that waited for an overlapping fetch already in flight
+    pub coalesced: usize,
+    pub candles_fetched: usize,
+}
+
+impl StoreStats {
+    pub fn add(&mut self, other: StoreStats) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.coalesced += other.coalesced;
+        self.candles_fetched += other.candles_fetched;
+    }
+}
+
+/// A range some caller is fetching right now; `done` closes when it finishes
+struct InFlight {
+    id: u64,
+    range: (u64, u64),
+    done: watch::Receiver<()>,
+}
+
+/// Gaps one caller fetches itself and the in-flight fetches it waits for
+#[derive(Default)]
+struct GapClaim<'a> {
+    claimed: Vec<(u64, u64)>,
+    waiting: Vec<watch::Receiver<()>>,
+    guard: Option<InFlightGuard<'a>>,
+}
+
+/// Unregisters a caller's in-flight ranges when its fetch finishes or is cancelled,
+/// then wakes everyone waiting on them by dropping the sender
+struct InFlightGuard<'a> {
+    store: &'a PriceStore,
+    key: (String, String),
+    id: u64,
+    _done: watch::Sender<()>,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.store.in_flight.lock().unwrap();
+        if let Some(fetches) = in_flight.get_mut(&self.key) {
+            fetches.retain(|f| f.id != self.id);
+        }
+    }
+}
+
+/// A series as loaded from disk, with the modification time of the file it was read from
+struct LoadedSeries {
+    series: StoredSeries,
+    modified: Option<SystemTime>,
+}
+
+/// Persistent candle store keyed by asset, interval and open time.
+/// One instance per directory is shared by every wallet and request in the process.
+/// Series are reloaded when their file changes on disk, and every write goes through
+/// a file lock, so other processes (such as `import_binance_klines`) can write to the
+/// same store.
+pub struct PriceStore {
+    dir: PathBuf,
+    series: Mutex<HashMap<(String, String), LoadedSeries>>,
+    in_flight: Mutex<HashMap<(String, String), Vec<InFlight>>>,
+    next_fetch_id: Mutex<u64>,
+}
+
+impl PriceStore {
+    /// Shared store for `dir`, created on first use
+    pub fn open(dir: &str) -> Arc<PriceStore> {
+        static STORES: OnceLock<Mutex<HashMap<PathBuf, Arc<PriceStore>>>> = OnceLock::new();
+
+        let dir = PathBuf::from(dir);
+        let mut stores = STORES.get_or_init(Default::default).lock().unwrap();
+        stores
+            .entry(dir.clone())
+            .or_insert_with(|| {
+                Arc::new(PriceStore {
+                    dir,
+                    series: Mutex::new(HashMap::new()),
+                    in_flight: Mutex::new(HashMap::new()),
+                    next_fetch_id: Mutex::new(0),
+                })
+            })
+            .clone()
+    }
+
+    fn path_for(&self, asset: &str, interval: &str) -> PathBuf {
+        self.dir.join(format!("{}_{}.json", asset, interval))
+    }
+
+    /// Run `f` on the in-memory series, (re)loading it from disk first if the file
+    /// is new or changed since it was read
+    fn with_series<T>(&self, asset: &str, interval: &str, f: impl FnOnce(&mut StoredSeries) -> T) -> T {
+        let path = self.path_for(asset, interval);
+        let modified = modified_time(&path);
+        let mut series = self.series.lock().unwrap();
+        let entry = series
+            .entry((asset.to_string(), interval.to_string()))
+            .or_insert_with(|| LoadedSeries {
+                series: load_series(&path),
+                modified,
+            });
+        if entry.modified != modified {
+            *entry = LoadedSeries {
+                series: load_series(&path),
+                modified,
+            };
+        }
+        f(&mut entry.series)
+    }
+
+    /// Candles already stored for `asset` with open times in `[start_ts, end_ts]`
+    pub fn stored_candles(&self, asset: &str, interval: &str, start_ts: u64, end_ts: u64) -> Vec<Candle> {
+        self.with_series(asset, interval, |s| {
+            s.candles.range(start_ts..=end_ts).map(|(_, c)| c.clone()).collect()
+        })
+    }
+
+    /// Merge candles into the store, mark `covered` as fetched and persist to disk.
+    /// The series is re-read from disk under a file lock first, so candles another
+    /// process wrote since it was loaded are kept.
+    pub fn insert(
+        &self,
+        asset: &str,
+        interval: &str,
+        candles: Vec<Candle>,
+        covered: (u64, u64),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.path_for(asset, interval);
+        let mut series = self.series.lock().unwrap();
+
+        fs::create_dir_all(&self.dir)?;
+        let lock = File::create(path.with_extension("lock"))?;
+        lock.lock()?;
+
+        let mut stored = load_series(&path);
+        for candle in candles {
+            stored.candles.insert(candle.open_time, candle);
+        }
+        stored.mark_covered(covered.0, covered.1);
+
+        // Written to a temporary file first so readers never see a partial series
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&stored)?)?;
+        fs::rename(&tmp, &path)?;
+
+        series.insert(
+            (asset.to_string(), interval.to_string()),
+            LoadedSeries {
+                series: stored,
+                modified: modified_time(&path),
+            },
+        );
+        Ok(())
+    }
+
+    /// Split the gaps of `[start_ts, end_ts]` into ranges this caller claims for fetching
+    /// and fetches already in flight that overlap them, which it waits for instead
+    fn claim_gaps<'a>(
+        &'a self,
+        asset: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> GapClaim<'a> {
+        let gaps = self.with_series(asset, interval, |s| s.gaps(start_ts, end_ts));
+        if gaps.is_empty() {
+            return GapClaim::default();
+        }
+
+        let key = (asset.to_string(), interval.to_string());
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let fetches = in_flight.entry(key.clone()).or_default();
+
+        let mut claimed = vec![];
+        let mut waiting = vec![];
+        for (gap_start, gap_end) in gaps {
+            let mut busy = StoredSeries::default();
+            for fetch in fetches.iter().filter(|f| f.range.0 <= gap_end && f.range.1 >= gap_start) {
+                busy.mark_covered(fetch.range.0, fetch.range.1);
+                waiting.push(fetch.done.clone());
+            }
+            claimed.extend(busy.gaps(gap_start, gap_end));
+        }
+
+        if claimed.is_empty() {
+            return GapClaim {
+                waiting,
+                ..Default::default()
+            };
+        }
+        let id = {
+            let mut next = self.next_fetch_id.lock().unwrap();
+            *next += 1;
+            *next
+        };
+        let (sender, receiver) = watch::channel(());
+        for &range in &claimed {
+            fetches.push(InFlight {
+                id,
+                range,
+                done: receiver.clone(),
+            });
+        }
+        let guard = InFlightGuard {
+            store: self,
+            key,
+            id,
+            _done: sender,
+        };
+        GapClaim {
+            claimed,
+            waiting,
+            guard: Some(guard),
+        }
+    }
+
+    /// Serve `[start_ts, end_ts]` from the store, calling `fetch` only for the gaps.
+    /// Gaps another caller is already fetching are waited for instead of fetched twice.
+    /// Ranges are only marked covered once a provider answered, and never past the
+    /// last closed candle, so they are retried later instead of cached as empty.
+    pub async fn candles<F, Fut>(
+        &self,
+        asset: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+        stats: &mut StoreStats,
+        fetch: F,
+    ) -> Option<PriceSeries>
+    where
+        F: Fn(u64, u64) -> Fut,
+        Fut: Future<Output = Option<PriceSeries>>,
+    {
+        // Candles of still-open intervals are returned but not persisted
+        let mut unsettled = vec![];
+        let mut fetched = false;
+        let mut coalesced = false;
+
+        // Gaps left after waiting (the other fetch failed or was cancelled) are claimed again
+        loop {
+            let GapClaim { claimed, waiting, guard } = self.claim_gaps(asset, interval, start_ts, end_ts);
+            if !claimed.is_empty() {
+                fetched = true;
+                let step = interval_secs(interval).unwrap_or(60);
+                let now = chrono::Utc::now().timestamp() as u64;
+                let last_closed = (now - now % step).saturating_sub(step);
+
+                for (gap_start, gap_end) in claimed {
+                    let Some(series) = fetch(gap_start, gap_end).await else {
+                        continue;
+                    };
+                    stats.candles_fetched += series.candles.len();
+
+                    let covered_end = gap_end.min(last_closed);
+                    let (candles, open): (Vec<_>, Vec<_>) = series
+                        .candles
+                        .into_iter()
+                        .partition(|c| c.open_time <= covered_end);
+                    unsettled.extend(open);
+                    if covered_end >= gap_start {
+                        if let Err(e) = self.insert(asset, interval, candles, (gap_start, covered_end)) {
+                            println!("⚠️  Failed to persist {} {} candles: {}", asset, interval, e);
+                        }
+                    }
+                }
+            }
+            drop(guard);
+
+            if waiting.is_empty() {
+                break;
+            }
+            coalesced = true;
+            for mut done in waiting {
+                // Errors once the fetching caller dropped its guard, which is what we wait for
+                while done.changed().await.is_ok() {}
+            }
+        }
+
+        if fetched {
+            stats.misses += 1;
+        } else if coalesced {
+            stats.coalesced += 1;
+        } else {
+            stats.hits += 1;
+        }
+
+        let mut candles = self.stored_candles(asset, interval, start_ts, end_ts);
+        candles.extend(unsettled);
+        candles.sort_by_key(|c| c.open_time);
+        if candles.is_empty() {
+            return None;
+        }
+        Some(PriceSeries {
+            interval: interval.to_string(),
+            candles,
+        })
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load_series(path: &Path) -> StoredSeries {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
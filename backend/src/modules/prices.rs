@@ -1,34 +1,56 @@
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
+use serde::Deserialize;
 use std::fs;
-use std::time::Duration;
 use crate::modules::utils::{get_priced_swaps_path};
-use crate::modules::types::{NamedSwap, PricedSwap, Settings};
+use crate::modules::types::{Candle, NamedSwap, PriceConfidence, PriceSampling, PricedLeg, PricedSwap, Settings};
+use crate::modules::stablecoins::{peg_fx_symbol, Stablecoin, StablecoinRegistry};
+use crate::modules::providers::{BinanceProvider, CompositePriceProvider, PriceProvider, PriceSeries};
+use crate::modules::implied_prices::ImpliedPriceProvider;
+use crate::modules::price_store::{PriceStore, StoreStats, DEFAULT_PRICE_STORE_DIR};
 
-const SOLANA_MINT: &str = "So11111111111111111111111111111111111111112";
-const BINANCE_SYMBOL: &str = "SOLUSDT";
+pub const SOLANA_MINT: &str = "So11111111111111111111111111111111111111112";
+/// Max distance between a swap and the candle used to price it
+const DEFAULT_PRICE_TOLERANCE_SECS: u64 = 90;
+/// Half-width of the 1s window used for VWAP sampling
+const VWAP_WINDOW_SECS: u64 = 5;
+/// Time groups priced at the same time
+const DEFAULT_PRICE_FETCH_CONCURRENCY: usize = 4;
+/// How far before the as-of time `last_prices` looks for a price
+const LAST_PRICE_LOOKBACK_SECS: u64 = 7 * 86400;
+/// Format of the priced swaps cache. Version 2 carries raw amounts; older caches held
+/// UI amounts, some scaled by their decimals twice, and are priced again.
+const PRICED_SWAPS_CACHE_VERSION: u32 = 2;
 
-fn group_by_time(swaps_with_token_names: &[NamedSwap]) -> Vec<Vec<&NamedSwap>> {
+#[derive(Deserialize)]
+struct PricedSwapsCache {
+    version: u32,
+    swaps: Vec<PricedSwap>,
+}
+
+/// Split items into groups spanning at most 6 hours, ordered by `timestamp`
+fn group_by_time<T>(items: &[T], timestamp: impl Fn(&T) -> u64) -> Vec<Vec<&T>> {
     const MAX_GROUP_SPAN: u64 = 6 * 3600; // 6 hours in seconds
 
-    let mut sorted = swaps_with_token_names.iter().collect::<Vec<_>>();
+    let mut sorted = items.iter().collect::<Vec<_>>();
     if sorted.is_empty() {
         return vec![];
     }
 
-    sorted.sort_by_key(|s| s.timestamp);
+    sorted.sort_by_key(|s| timestamp(s));
 
     let mut groups = vec![];
     let mut current_group = vec![sorted[0]];
-    let mut group_start = sorted[0].timestamp;
+    let mut group_start = timestamp(sorted[0]);
 
     for s in sorted.iter().skip(1) {
-        let span = s.timestamp - group_start;
+        let span = timestamp(s) - group_start;
 
         if span > MAX_GROUP_SPAN {
             groups.push(current_group);
             current_group = vec![*s];
-            group_start = s.timestamp;
+            group_start = timestamp(s);
         } else {
             current_group.push(*s);
         }
@@ -41,36 +63,616 @@ fn group_by_time(swaps_with_token_names: &[NamedSwap]) -> Vec<Vec<&NamedSwap>> {
     groups
 }
 
-async fn fetch_price_map_for_range(client: &Client, start_ts: u64, end_ts: u64) -> Result<HashMap<u64, f64>, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.binance.com/api/v3/klines?symbol={}&interval=1m&startTime={}&endTime={}",
-        BINANCE_SYMBOL,
-        start_ts * 1000,
-        end_ts * 1000
-    );
+/// Price series fetched for one time group, keyed by mint (or Binance FX symbol)
+type SeriesCache = HashMap<String, Option<PriceSeries>>;
+
+/// 1m candles for `mint`, served from the local price store; providers only fill gaps
+async fn fetch_price_map_for_range(
+    ctx: &mut PricingContext<'_>,
+    mint: &str,
+    start_ts: u64,
+    end_ts: u64,
+) -> Option<PriceSeries> {
+    let providers = ctx.providers;
+    ctx.store
+        .candles(mint, "1m", start_ts, end_ts, &mut ctx.stats, |from, to| {
+            providers.candles(mint, "1m", from, to)
+        })
+        .await
+}
+
+/// Cached series for `mint` over the group range, fetched on first use
+async fn series_for<'a>(
+    ctx: &'a mut PricingContext<'_>,
+    mint: &str,
+    range: (u64, u64),
+) -> Option<&'a PriceSeries> {
+    if !ctx.series.contains_key(mint) {
+        let series = fetch_price_map_for_range(ctx, mint, range.0, range.1).await;
+        ctx.series.insert(mint.to_string(), series);
+    }
+    ctx.series.get(mint).and_then(|s| s.as_ref())
+}
+
+/// A USD valuation of one swap leg and how it was obtained
+struct Quote {
+    usd_value: f64,
+    method: String,
+    price_timestamp: Option<u64>,
+    distance_secs: Option<u64>,
+    interpolation: String,
+    confidence: PriceConfidence,
+}
+
+impl Quote {
+    /// Value `amount` from the candle matching `sampling`, or explain why no candle can be used.
+    /// `method` gives the base method name for the candle, e.g. "binance_1m".
+    fn from_candle(
+        candles: &[Candle],
+        amount: f64,
+        timestamp: u64,
+        tolerance_secs: u64,
+        sampling: PriceSampling,
+        method: impl FnOnce(&Candle) -> String,
+    ) -> Result<Quote, String> {
+        let candle = candle_for(candles, timestamp, sampling).ok_or("empty price series")?;
+        let distance = timestamp.abs_diff(candle.open_time);
+        if distance > tolerance_secs {
+            return Err(format!(
+                "nearest candle is {}s away (tolerance {}s)",
+                distance, tolerance_secs
+            ));
+        }
+        Ok(Quote {
+            usd_value: amount * sample_candle(candle, timestamp, sampling),
+            method: sampling.method_name(&method(candle)),
+            price_timestamp: Some(candle.open_time),
+            distance_secs: Some(distance),
+            interpolation: sampling.interpolation().to_string(),
+            confidence: candle_confidence(candle, distance),
+        })
+    }
+}
+
+/// The 1m candle whose open time is nearest to `timestamp`
+fn nearest_candle(candles: &[Candle], timestamp: u64) -> Option<&Candle> {
+    candles
+        .iter()
+        .min_by_key(|c| (timestamp.abs_diff(c.open_time), c.open_time))
+}
+
+/// Candle to sample: the nearest one for close prices, otherwise the candle the swap
+/// happened in (falling back to the nearest one if the series starts after the swap)
+fn candle_for(candles: &[Candle], timestamp: u64, sampling: PriceSampling) -> Option<&Candle> {
+    match sampling {
+        PriceSampling::Close => nearest_candle(candles, timestamp),
+        _ => candles
+            .iter()
+            .rev()
+            .find(|c| c.open_time <= timestamp)
+            .or_else(|| nearest_candle(candles, timestamp)),
+    }
+}
+
+/// Price at `timestamp` sampled from a 1m candle
+fn sample_candle(candle: &Candle, timestamp: u64, sampling: PriceSampling) -> f64 {
+    match sampling {
+        PriceSampling::Close | PriceSampling::Vwap1s => candle.close,
+        PriceSampling::Linear => {
+            let elapsed = timestamp.saturating_sub(candle.open_time).min(60) as f64;
+            candle.open + (candle.close - candle.open) * elapsed / 60.0
+        }
+        PriceSampling::OhlcMid => (candle.high + candle.low) / 2.0,
+    }
+}
+
+/// VWAP of the 1s candles within `VWAP_WINDOW_SECS` of the swap, if any traded
+async fn vwap_1s_quote(
+    ctx: &mut PricingContext<'_>,
+    mint: &str,
+    amount: f64,
+    timestamp: u64,
+) -> Option<Quote> {
+    let providers = ctx.providers;
+    let series = ctx
+        .store
+        .candles(
+            mint,
+            "1s",
+            timestamp.saturating_sub(VWAP_WINDOW_SECS),
+            timestamp + VWAP_WINDOW_SECS,
+            &mut ctx.stats,
+            |from, to| providers.candles(mint, "1s", from, to),
+        )
+        .await?;
+
+    let volume: f64 = series.candles.iter().map(|c| c.volume).sum();
+    if volume <= 0.0 {
+        return None;
+    }
+    let vwap = series
+        .candles
+        .iter()
+        .map(|c| (c.high + c.low + c.close) / 3.0 * c.volume)
+        .sum::<f64>()
+        / volume;
+
+    let nearest = nearest_candle(&series.candles, timestamp)?;
+    let distance = timestamp.abs_diff(nearest.open_time);
+    Some(Quote {
+        usd_value: amount * vwap,
+        method: format!("{}_1s_vwap", nearest.source),
+        price_timestamp: Some(nearest.open_time),
+        distance_secs: Some(distance),
+        interpolation: PriceSampling::Vwap1s.interpolation().to_string(),
+        confidence: candle_confidence(nearest, distance),
+    })
+}
+
+/// High within a minute of the swap, medium up to the tolerance;
+/// Jupiter spot prices are always low since they are not historical,
+/// implied prices since they come from a handful of swaps
+fn candle_confidence(candle: &Candle, distance_secs: u64) -> PriceConfidence {
+    if candle.source == "jupiter" || candle.source == "implied" {
+        PriceConfidence::Low
+    } else if distance_secs <= 60 {
+        PriceConfidence::High
+    } else {
+        PriceConfidence::Medium
+    }
+}
+
+/// USD value of `amount` units of a stablecoin
+async fn stable_usd_value(
+    stable: &Stablecoin,
+    amount: f64,
+    timestamp: u64,
+    ctx: &mut PricingContext<'_>,
+    range: (u64, u64),
+) -> Result<Quote, String> {
+    let tolerance = ctx.tolerance_secs;
+    let sampling = ctx.sampling.for_1m();
+    if ctx.registry.price_at_market() {
+        if let Some(series) = series_for(ctx, &stable.mint, range).await {
+            if let Ok(quote) = Quote::from_candle(&series.candles, amount, timestamp, tolerance, sampling, |c| {
+                format!("{}_1m", c.source)
+            }) {
+                return Ok(quote);
+            }
+        }
+    }
+
+    if stable.peg == "USD" {
+        return Ok(Quote {
+            usd_value: amount,
+            method: "usd_direct".to_string(),
+            price_timestamp: None,
+            distance_secs: None,
+            interpolation: "peg".to_string(),
+            confidence: PriceConfidence::High,
+        });
+    }
+
+    let Some(symbol) = peg_fx_symbol(&stable.peg) else {
+        return Err(format!("no FX pair known for peg currency {}", stable.peg));
+    };
+    if !ctx.series.contains_key(symbol) {
+        let fx = ctx.fx;
+        let offline = ctx.offline;
+        let series = ctx
+            .store
+            .candles(symbol, "1m", range.0, range.1, &mut ctx.stats, |from, to| async move {
+                if offline {
+                    return None;
+                }
+                match fx.klines(symbol, "1m", from, to).await {
+                    Ok(candles) if !candles.is_empty() => Some(PriceSeries {
+                        interval: "1m".to_string(),
+                        candles,
+                    }),
+                    Ok(_) => None,
+                    Err(e) => {
+                        println!("⚠️  Failed to fetch {} rates: {}", symbol, e);
+                        None
+                    }
+                }
+            })
+            .await;
+        ctx.series.insert(symbol.to_string(), series);
+    }
+    let series = ctx
+        .series
+        .get(symbol)
+        .and_then(|s| s.as_ref())
+        .ok_or_else(|| format!("no {} rates available", symbol))?;
+    let mut quote = Quote::from_candle(&series.candles, amount, timestamp, tolerance, sampling, |_| {
+        "stable_peg".to_string()
+    })?;
+    quote.interpolation = format!("peg_{}", quote.interpolation);
+    Ok(quote)
+}
 
-    let resp = client
-        .get(&url)
-        .timeout(Duration::from_secs(10))
-        .send().await?
-        .json::<Vec<Vec<serde_json::Value>>>().await?;
-
-    let mut map = HashMap::new();
-    for entry in resp {
-        if let (Some(open_time), Some(close_price)) = (
-            entry.get(0).and_then(|v| v.as_u64()),
-            entry.get(4).and_then(|v| v.as_str()).and_then(|s| s.parse::<f64>().ok()),
-        ) {
-            map.insert(open_time / 1000, close_price);
+/// USD value of one swap leg
+async fn leg_usd_value(
+    mint: &str,
+    amount: f64,
+    timestamp: u64,
+    ctx: &mut PricingContext<'_>,
+    range: (u64, u64),
+) -> Result<Quote, String> {
+    if let Some(stable) = ctx.registry.get(mint) {
+        return stable_usd_value(stable, amount, timestamp, ctx, range).await;
+    }
+    if ctx.sampling == PriceSampling::Vwap1s {
+        if let Some(quote) = vwap_1s_quote(ctx, mint, amount, timestamp).await {
+            return Ok(quote);
         }
     }
 
-    Ok(map)
+    let tolerance = ctx.tolerance_secs;
+    let sampling = ctx.sampling.for_1m();
+    let quote = match series_for(ctx, mint, range).await {
+        Some(series) => Quote::from_candle(&series.candles, amount, timestamp, tolerance, sampling, |c| {
+            format!("{}_1m", c.source)
+        }),
+        None => Err("no provider has prices for this mint".to_string()),
+    };
+    match quote {
+        Err(reason) => implied_quote(ctx, mint, amount, timestamp, range).await.ok_or(reason),
+        quote => quote,
+    }
+}
+
+/// Value a leg from prices implied by earlier swaps, if enabled
+async fn implied_quote(
+    ctx: &mut PricingContext<'_>,
+    mint: &str,
+    amount: f64,
+    timestamp: u64,
+    range: (u64, u64),
+) -> Option<Quote> {
+    let implied = ctx.implied?;
+    let key = format!("implied:{}", mint);
+    if !ctx.series.contains_key(&key) {
+        let series = match implied.candles(mint, "1m", range.0, range.1).await {
+            Ok(Some(candles)) => Some(PriceSeries {
+                interval: "1m".to_string(),
+                candles,
+            }),
+            Ok(None) => None,
+            Err(e) => {
+                println!("⚠️  implied price provider failed for {}: {}", mint, e);
+                None
+            }
+        };
+        ctx.series.insert(key.clone(), series);
+    }
+    let series = ctx.series.get(&key)?.as_ref()?;
+    Quote::from_candle(&series.candles, amount, timestamp, ctx.tolerance_secs, ctx.sampling.for_1m(), |c| {
+        format!("{}_1m", c.source)
+    })
+    .ok()
+}
+
+/// Approximate USD volume traded over a series, used to compare leg liquidity
+fn usd_volume(series: &PriceSeries) -> f64 {
+    series.candles.iter().map(|c| c.volume * c.close).sum()
+}
+
+/// Value a swap through its most reliable leg: stables first, then SOL,
+/// then whichever remaining token traded more USD volume over the group range.
+/// If no leg can be priced, the reasons for each leg are returned instead.
+async fn price_swap(
+    swap: &NamedSwap,
+    sold_amount: f64,
+    bought_amount: f64,
+    ctx: &mut PricingContext<'_>,
+    range: (u64, u64),
+) -> Result<(Quote, PricedLeg), String> {
+    let rank = |mint: &str| {
+        if ctx.registry.is_stable(mint) {
+            0
+        } else if mint == SOLANA_MINT {
+            1
+        } else {
+            2
+        }
+    };
+
+    let mut legs = vec![
+        (rank(&swap.sold_mint), 0.0, PricedLeg::Sold, swap.sold_mint.as_str(), sold_amount),
+        (rank(&swap.bought_mint), 0.0, PricedLeg::Bought, swap.bought_mint.as_str(), bought_amount),
+    ];
+
+    if legs.iter().all(|leg| leg.0 == 2) {
+        for leg in legs.iter_mut() {
+            leg.1 = series_for(ctx, leg.3, range)
+                .await
+                .map(usd_volume)
+                .unwrap_or(0.0);
+        }
+    }
+    legs.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
 
+    let mut reasons = vec![];
+    for (_, _, leg, mint, amount) in legs {
+        match leg_usd_value(mint, amount, swap.timestamp, ctx, range).await {
+            Ok(quote) => return Ok((quote, leg)),
+            Err(reason) => reasons.push(format!("{} leg: {}", leg.as_str(), reason)),
+        }
+    }
+    Err(reasons.join("; "))
+}
+
+/// Price the swaps of one time group with its own series cache
+async fn price_group(group: Vec<&NamedSwap>, mut ctx: PricingContext<'_>) -> (Vec<PricedSwap>, StoreStats) {
+    let start_ts = group.first().unwrap().timestamp.saturating_sub(120);
+    let end_ts = group.last().unwrap().timestamp + 60;
+    let range = (start_ts, end_ts);
+    let mut results = vec![];
+
+    for swap in group {
+        // Helius `tokenAmount` is already a UI amount; scaling it by decimals again would
+        // shrink it by 10^decimals
+        let (sold_amount, bought_amount) = (swap.sold_amount, swap.bought_amount);
+
+        let priced = price_swap(swap, sold_amount, bought_amount, &mut ctx, range).await;
+
+        let (quote, priced_leg, unpriced_reason) = match priced {
+            Ok((quote, leg)) => (Some(quote), Some(leg), None),
+            Err(reason) => {
+                println!(
+                    "No price found for swap at ts={} (sig={}): {}",
+                    swap.timestamp, swap.signature, reason
+                );
+                (None, None, Some(reason))
+            }
+        };
+
+        results.push(PricedSwap {
+            timestamp: swap.timestamp,
+            signature: swap.signature.clone(),
+            sold_mint: swap.sold_mint.clone(),
+            sold_token_name: swap.sold_token_name.clone(),
+            sold_amount,
+            sold_decimals: swap.sold_decimals,
+            bought_mint: swap.bought_mint.clone(),
+            bought_token_name: swap.bought_token_name.clone(),
+            bought_amount,
+            bought_decimals: swap.bought_decimals,
+            sold_raw: swap.sold_raw.clone(),
+            bought_raw: swap.bought_raw.clone(),
+            venue: swap.venue.clone(),
+            usd_value: quote.as_ref().map(|q| q.usd_value),
+            pricing_method: quote
+                .as_ref()
+                .map(|q| q.method.clone())
+                .unwrap_or_else(|| "unpriced".to_string()),
+            priced_leg,
+            price_timestamp: quote.as_ref().and_then(|q| q.price_timestamp),
+            price_distance_secs: quote.as_ref().and_then(|q| q.distance_secs),
+            interpolation: quote.as_ref().map(|q| q.interpolation.clone()),
+            confidence: quote.as_ref().map(|q| q.confidence).unwrap_or_default(),
+            unpriced_reason,
+            base_currency: String::new(),
+            base_value: None,
+            fx_rate: None,
+            binance_sol_usd_price: None, // optional to fill if needed
+        });
+    }
+
+    (results, ctx.stats)
+}
+
+/// Price every swap with the given sampling mode, keeping unpriced swaps with a reason.
+/// Time groups are priced concurrently, up to `price_fetch_concurrency` at once,
+/// and results keep the group order.
+pub async fn price_swaps(
+    swaps_with_token_names: &[NamedSwap],
+    settings: &Settings,
+    sampling: PriceSampling,
+) -> Vec<PricedSwap> {
+    let providers = CompositePriceProvider::from_settings(settings);
+    let fx = BinanceProvider::new(Client::new(), settings);
+    let implied = ImpliedPriceProvider::from_settings(settings);
+    let store = PriceStore::open(
+        settings.config.price_store_dir.as_deref().unwrap_or(DEFAULT_PRICE_STORE_DIR),
+    );
+    let offline = settings.config.offline_pricing.unwrap_or(false);
+    let tolerance_secs = settings.config.price_tolerance_secs.unwrap_or(DEFAULT_PRICE_TOLERANCE_SECS);
+    let concurrency = settings
+        .config
+        .price_fetch_concurrency
+        .unwrap_or(DEFAULT_PRICE_FETCH_CONCURRENCY)
+        .max(1);
+
+    // Futures are lazy, so building them all up front only starts `concurrency` at a time
+    let mut group_futures = vec![];
+    for group in group_by_time(swaps_with_token_names, |s| s.timestamp) {
+        let ctx = PricingContext {
+            registry: &settings.stablecoins,
+            providers: &providers,
+            fx: &fx,
+            implied: implied.as_ref(),
+            store: &store,
+            stats: StoreStats::default(),
+            offline,
+            tolerance_secs,
+            sampling,
+            series: HashMap::new(),
+        };
+        group_futures.push(price_group(group, ctx));
+    }
+    let priced_groups: Vec<(Vec<PricedSwap>, StoreStats)> = stream::iter(group_futures)
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let mut stats = StoreStats::default();
+    let mut results = vec![];
+    for (priced, group_stats) in priced_groups {
+        results.extend(priced);
+        stats.add(group_stats);
+    }
+
+    let unpriced = results.iter().filter(|s| s.usd_value.is_none()).count();
+    println!(
+        "Price store: {} hits, {} misses, {} coalesced, {} candles fetched ({} swaps priced, {} unpriced)",
+        stats.hits,
+        stats.misses,
+        stats.coalesced,
+        stats.candles_fetched,
+        results.len() - unpriced,
+        unpriced
+    );
+
+    results
+}
+
+/// Market USD price of one unit of `mint` at each `(mint, timestamp)`, in input order.
+/// Uses the price store and providers only, never implied prices.
+pub async fn market_prices(points: &[(String, u64)], settings: &Settings) -> Vec<Option<f64>> {
+    let providers = CompositePriceProvider::from_settings(settings);
+    let fx = BinanceProvider::new(Client::new(), settings);
+    let store = PriceStore::open(
+        settings.config.price_store_dir.as_deref().unwrap_or(DEFAULT_PRICE_STORE_DIR),
+    );
+    let offline = settings.config.offline_pricing.unwrap_or(false);
+    let tolerance_secs = settings.config.price_tolerance_secs.unwrap_or(DEFAULT_PRICE_TOLERANCE_SECS);
+    let sampling = settings.config.price_sampling.unwrap_or_default();
+    let concurrency = settings
+        .config
+        .price_fetch_concurrency
+        .unwrap_or(DEFAULT_PRICE_FETCH_CONCURRENCY)
+        .max(1);
+
+    let indexed: Vec<(usize, &str, u64)> = points
+        .iter()
+        .enumerate()
+        .map(|(i, (mint, ts))| (i, mint.as_str(), *ts))
+        .collect();
+
+    let mut group_futures = vec![];
+    for group in group_by_time(&indexed, |p| p.2) {
+        let mut ctx = PricingContext {
+            registry: &settings.stablecoins,
+            providers: &providers,
+            fx: &fx,
+            implied: None,
+            store: &store,
+            stats: StoreStats::default(),
+            offline,
+            tolerance_secs,
+            sampling,
+            series: HashMap::new(),
+        };
+        group_futures.push(async move {
+            let range = (group.first().unwrap().2.saturating_sub(120), group.last().unwrap().2 + 60);
+            let mut prices = vec![];
+            for &&(i, mint, timestamp) in &group {
+                let price = leg_usd_value(mint, 1.0, timestamp, &mut ctx, range).await.ok();
+                prices.push((i, price.map(|q| q.usd_value)));
+            }
+            prices
+        });
+    }
+    let priced_groups: Vec<Vec<(usize, Option<f64>)>> = stream::iter(group_futures)
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let mut prices = vec![None; points.len()];
+    for (i, price) in priced_groups.into_iter().flatten() {
+        prices[i] = price;
+    }
+    prices
+}
+
+/// Last known USD price of each mint at or before `as_of` (looking back a week), with the
+/// open time of the hourly candle it came from. Stablecoins are priced like swap legs:
+/// at their market with `price_at_market`, otherwise at their peg, at the time itself.
+pub async fn last_prices(mints: &[String], as_of: u64, settings: &Settings) -> Vec<Option<(f64, u64)>> {
+    last_prices_at(mints, &[as_of], settings)
+        .await
+        .into_iter()
+        .map(|prices| prices.into_iter().next().flatten())
+        .collect()
+}
+
+/// `last_prices` of each mint at every one of `marks`. The candles of a mint are loaded
+/// once for the whole range, so a mint without prices is only looked up once.
+pub async fn last_prices_at(mints: &[String], marks: &[u64], settings: &Settings) -> Vec<Vec<Option<(f64, u64)>>> {
+    let (Some(&first_mark), Some(&last_mark)) = (marks.iter().min(), marks.iter().max()) else {
+        return vec![vec![]; mints.len()];
+    };
+    let providers = CompositePriceProvider::from_settings(settings);
+    let store = PriceStore::open(
+        settings.config.price_store_dir.as_deref().unwrap_or(DEFAULT_PRICE_STORE_DIR),
+    );
+    let concurrency = settings
+        .config
+        .price_fetch_concurrency
+        .unwrap_or(DEFAULT_PRICE_FETCH_CONCURRENCY)
+        .max(1);
+    let start_ts = first_mark.saturating_sub(LAST_PRICE_LOOKBACK_SECS);
+
+    let mut mint_futures = vec![];
+    for mint in mints {
+        let providers = &providers;
+        let store = &store;
+        mint_futures.push(async move {
+            if settings.stablecoins.get(mint).is_some() {
+                let points: Vec<(String, u64)> = marks.iter().map(|&mark| (mint.clone(), mark)).collect();
+                return market_prices(&points, settings)
+                    .await
+                    .into_iter()
+                    .zip(marks)
+                    .map(|(price, &mark)| price.map(|price| (price, mark)))
+                    .collect();
+            }
+            let mut stats = StoreStats::default();
+            let candles = store
+                .candles(mint, "1h", start_ts, last_mark, &mut stats, |from, to| {
+                    providers.candles(mint, "1h", from, to)
+                })
+                .await
+                .map(|series| series.candles)
+                .unwrap_or_default();
+            marks
+                .iter()
+                .map(|&mark| {
+                    candles
+                        .iter()
+                        .rev()
+                        .find(|c| c.open_time <= mark && mark - c.open_time <= LAST_PRICE_LOOKBACK_SECS)
+                        .map(|c| (c.close, c.open_time))
+                })
+                .collect::<Vec<_>>()
+        });
+    }
+
+    stream::iter(mint_futures)
+        .buffered(concurrency)
+        .collect()
+        .await
+}
+
+/// Everything the pricing loop needs besides the swap itself
+struct PricingContext<'a> {
+    registry: &'a StablecoinRegistry,
+    providers: &'a CompositePriceProvider,
+    fx: &'a BinanceProvider,
+    implied: Option<&'a ImpliedPriceProvider>,
+    store: &'a PriceStore,
+    stats: StoreStats,
+    offline: bool,
+    tolerance_secs: u64,
+    sampling: PriceSampling,
+    series: SeriesCache,
 }
 
 pub async fn get_or_load_swaps_with_prices(
-    swaps_with_token_names: &Vec<NamedSwap>,
+    swaps_with_token_names: &[NamedSwap],
     settings: &Settings,
 ) -> Result<Vec<PricedSwap>, Box<dyn std::error::Error>> {
     let wallet_address = settings.wallet_address.to_lowercase();
@@ -79,16 +681,25 @@ pub async fn get_or_load_swaps_with_prices(
     let write_cache_files = settings.config.write_cache_files.unwrap_or(false);
 
     if use_cached_priced_swaps && std::path::Path::new(&priced_swaps_path).exists() {
-        println!("♻️  Using cached enriched swaps from {}", priced_swaps_path);
         let content = fs::read_to_string(&priced_swaps_path)?;
-        let swaps: Vec<PricedSwap> = serde_json::from_str(&content)?;
-        return Ok(swaps);
+        match serde_json::from_str::<PricedSwapsCache>(&content) {
+            Ok(cache) if cache.version == PRICED_SWAPS_CACHE_VERSION => {
+                // A cache priced for a shorter window lacks the older swaps
+                let cached: HashSet<&str> = cache.swaps.iter().map(|s| s.signature.as_str()).collect();
+                if swaps_with_token_names.iter().all(|s| cached.contains(s.signature.as_str())) {
+                    println!("♻️  Using cached enriched swaps from {}", priced_swaps_path);
+                    return Ok(cache.swaps);
+                }
+                println!("♻️  Cache {} misses swaps of the analysis window, pricing swaps again", priced_swaps_path);
+            }
+            _ => println!("♻️  Ignoring outdated cache {}, pricing swaps again", priced_swaps_path),
+        }
     }
 
-    let groups = group_by_time(swaps_with_token_names);
+    let groups = group_by_time(swaps_with_token_names, |s| s.timestamp);
     println!(
-        "{:<6} | {:<20} | {:<20} | {}",
-        "Group", "Start Time", "End Time", "Swaps"
+        "{:<6} | {:<20} | {:<20} | Swaps",
+        "Group", "Start Time", "End Time"
     );
     println!("{}", "-".repeat(65));
     for (i, group) in groups.iter().enumerate() {
@@ -97,106 +708,14 @@ pub async fn get_or_load_swaps_with_prices(
         println!("{:<6} | {:<20} | {:<20} | {}", i + 1, start_ts, end_ts, group.len());
     }
 
-    let client = Client::new();
-    let mut results = vec![];
-
-    for group in groups {
-        let start_ts = group.first().unwrap().timestamp.saturating_sub(120);
-        let end_ts = group.last().unwrap().timestamp + 60;
-        let price_map = fetch_price_map_for_range(&client, start_ts, end_ts).await?;
-
-        for swap in group {
-            let sold_amount = if let Some(dec) = swap.sold_decimals {
-                swap.sold_amount / 10f64.powi(dec as i32)
-            } else {
-                swap.sold_amount
-            };
-            let bought_amount = if let Some(dec) = swap.bought_decimals {
-                swap.bought_amount / 10f64.powi(dec as i32)
-            } else {
-                swap.bought_amount
-            };
-
-            let pricing_method;
-            let usd_value;
-
-            if swap.sold_token_name.contains("USD") {
-                usd_value = sold_amount;
-                pricing_method = "usd_direct".to_string();
-            } else if swap.bought_token_name.contains("USD") {
-                usd_value = bought_amount;
-                pricing_method = "usd_direct".to_string();
-            } else {
-                let mut timestamps: Vec<_> = price_map.keys().cloned().collect();
-                timestamps.sort_unstable();
-                let mut min_diff = u64::MAX;
-                let mut best_ts = None;
-                for ts in &timestamps {
-                    let diff = swap.timestamp.abs_diff(*ts);
-                    if diff < min_diff {
-                        min_diff = diff;
-                        best_ts = Some(*ts);
-                    }
-                }
-                let matched_price = best_ts.and_then(|ts| {
-                    if min_diff <= 90 {
-                        price_map.get(&ts).copied()
-                    } else {
-                        None
-                    }
-                });
-
-                if let Some(price) = matched_price {
-                    usd_value = if swap.sold_mint == SOLANA_MINT {
-                        sold_amount * price
-                    } else {
-                        bought_amount * price
-                    };
-                    pricing_method = "binance_1m".to_string();
-                } else {
-                    println!(
-                        "No price found for swap at ts={} (sig={})",
-                        swap.timestamp, swap.signature
-                    );
-                    continue;
-                }
-            }
-
-            results.push(PricedSwap {
-                timestamp: swap.timestamp,
-                signature: swap.signature.clone(),
-                sold_mint: swap.sold_mint.clone(),
-                sold_token_name: swap.sold_token_name.clone(),
-                sold_amount,
-                sold_decimals: swap.sold_decimals,
-                bought_mint: swap.bought_mint.clone(),
-                bought_token_name: swap.bought_token_name.clone(),
-                bought_amount,
-                bought_decimals: swap.bought_decimals,
-                usd_value: Some(usd_value),
-                pricing_method,
-                binance_sol_usd_price: None, // optional to fill if needed
-            });
-        }
-    }
-
-    let mut count_binance = 0;
-    let mut count_usd_direct = 0;
-    for swap in &results {
-        match swap.pricing_method.as_str() {
-            "binance_1m" => count_binance += 1,
-            "usd_direct" => count_usd_direct += 1,
-            _ => {}
-        }
-    }
-
-    println!(
-        "Pricing summary: binance_1m: {}, usd_direct: {}",
-        count_binance, count_usd_direct
-    );
+    let sampling = settings.config.price_sampling.unwrap_or_default();
+    let results = price_swaps(swaps_with_token_names, settings, sampling).await;
 
     if write_cache_files {
-        let json = serde_json::to_string_pretty(&results)?;
+        let json = serde_json::to_string_pretty(&serde_json::json!({
+            "version": PRICED_SWAPS_CACHE_VERSION,
+            "swaps": &results,
+        }))?;
         fs::write(&priced_swaps_path, json)?;
         println!("✅ Saved enriched swaps to {}", priced_swaps_path);
     }
@@ -0,0 +1,525 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::modules::prices::SOLANA_MINT;
+use crate::modules::transactions::AnyError;
+use crate::modules::types::{Candle, Settings};
+
+const DEFAULT_PROVIDER_ORDER: &[&str] = &["local", "binance", "birdeye", "jupiter"];
+const DEFAULT_LOCAL_PRICE_DIR: &str = "data/prices";
+/// Binance and Birdeye both cap a single kline/OHLCV response at 1000 candles
+const MAX_CANDLES_PER_REQUEST: usize = 1000;
+/// Request budgets used when `provider_requests_per_sec` does not list a provider
+const DEFAULT_REQUESTS_PER_SEC: &[(&str, f64)] = &[
+    ("binance", 10.0),
+    ("birdeye", 1.0),
+    ("jupiter", 5.0),
+    ("ecb", 5.0),
+];
+
+/// Length of a kline interval such as "1s", "1m" or "1h" in seconds;
+/// `None` if it does not parse, overflows or is zero
+pub fn interval_secs(interval: &str) -> Option<u64> {
+    let unit = interval.chars().last()?;
+    let value: u64 = interval[..interval.len() - unit.len_utf8()].parse().ok()?;
+    let unit_secs: u64 = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        _ => return None,
+    };
+    value.checked_mul(unit_secs).filter(|&secs| secs > 0)
+}
+
+/// Spaces out requests to one provider. Limiters are shared per provider name,
+/// so concurrent groups and requests stay within the same budget.
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: tokio::sync::Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Shared limiter for `provider`, rated from `provider_requests_per_sec` in config.
+    /// Limiters are keyed by provider and rate, so a changed rate takes effect with the
+    /// next request that loads it.
+    pub fn shared(provider: &str, settings: &Settings) -> Arc<RateLimiter> {
+        /// Provider name and the bits of its requests-per-second rate
+        type LimiterKey = (String, u64);
+        static LIMITERS: OnceLock<Mutex<HashMap<LimiterKey, Arc<RateLimiter>>>> = OnceLock::new();
+
+        let per_sec = settings
+            .config
+            .provider_requests_per_sec
+            .as_ref()
+            .and_then(|limits| limits.get(provider).copied())
+            .or_else(|| {
+                DEFAULT_REQUESTS_PER_SEC
+                    .iter()
+                    .find(|(name, _)| *name == provider)
+                    .map(|(_, rate)| *rate)
+            })
+            .unwrap_or(5.0)
+            .max(0.01);
+
+        let mut limiters = LIMITERS.get_or_init(Default::default).lock().unwrap();
+        limiters
+            .entry((provider.to_string(), per_sec.to_bits()))
+            .or_insert_with(|| {
+                Arc::new(RateLimiter {
+                    interval: Duration::from_secs_f64(1.0 / per_sec),
+                    next_slot: tokio::sync::Mutex::new(Instant::now()),
+                })
+            })
+            .clone()
+    }
+
+    /// Wait for the next free request slot
+    pub async fn wait(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Source of historical USD candles for a token mint
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    /// Short identifier recorded in `PricedSwap.pricing_method`, e.g. "binance"
+    fn name(&self) -> &str;
+
+    /// USD candles for `mint` with open times in `[start_ts, end_ts]`.
+    /// Returns `Ok(None)` if the provider does not cover this mint or interval.
+    async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError>;
+}
+
+/// Candles of the inverse pair, e.g. USDT in USDC from USDCUSDT; volume in the new base
+pub fn invert_candles(candles: Vec<Candle>) -> Vec<Candle> {
+    candles
+        .into_iter()
+        .filter(|c| c.open > 0.0 && c.high > 0.0 && c.low > 0.0 && c.close > 0.0)
+        .map(|c| Candle {
+            open: 1.0 / c.open,
+            high: 1.0 / c.low,
+            low: 1.0 / c.high,
+            close: 1.0 / c.close,
+            volume: c.volume * c.close,
+            ..c
+        })
+        .collect()
+}
+
+/// Binance spot klines, quoted in USDT
+pub struct BinanceProvider {
+    client: Client,
+    /// Symbol per mint, and whether the mint is the pair's quote asset
+    symbols: HashMap<String, (String, bool)>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl BinanceProvider {
+    pub fn new(client: Client, settings: &Settings) -> Self {
+        let mut symbols = HashMap::from([(SOLANA_MINT.to_string(), ("SOLUSDT".to_string(), false))]);
+        for stable in settings.stablecoins.all() {
+            if let Some(symbol) = &stable.market_symbol {
+                symbols.insert(stable.mint.clone(), (symbol.clone(), stable.market_inverted()));
+            }
+        }
+        BinanceProvider {
+            client,
+            symbols,
+            limiter: RateLimiter::shared("binance", settings),
+        }
+    }
+
+    /// Raw klines for a Binance symbol, paginated over the whole range
+    pub async fn klines(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Vec<Candle>, AnyError> {
+        let step = interval_secs(interval).ok_or_else(|| format!("Unknown interval {}", interval))?;
+        let mut candles = vec![];
+        let mut cursor = start_ts;
+
+        while cursor <= end_ts {
+            let url = format!(
+                "https://api.binance.com/api/v3/klines?symbol={}&interval={}&startTime={}&endTime={}&limit={}",
+                symbol,
+                interval,
+                cursor * 1000,
+                end_ts * 1000,
+                MAX_CANDLES_PER_REQUEST
+            );
+
+            self.limiter.wait().await;
+            let resp = self.client
+                .get(&url)
+                .timeout(Duration::from_secs(10))
+                .send().await?
+                .error_for_status()?
+                .json::<Vec<Vec<Value>>>().await?;
+
+            let batch_len = resp.len();
+            for entry in resp {
+                let field = |i: usize| entry.get(i).and_then(|v| v.as_str()).and_then(|s| s.parse::<f64>().ok());
+                if let (Some(open_time), Some(open), Some(high), Some(low), Some(close), Some(volume)) = (
+                    entry.first().and_then(|v| v.as_u64()),
+                    field(1),
+                    field(2),
+                    field(3),
+                    field(4),
+                    field(5),
+                ) {
+                    candles.push(Candle {
+                        open_time: open_time / 1000,
+                        open,
+                        high,
+                        low,
+                        close,
+                        volume,
+                        source: "binance".to_string(),
+                    });
+                }
+            }
+
+            match candles.last() {
+                Some(last) if batch_len == MAX_CANDLES_PER_REQUEST => cursor = last.open_time + step,
+                _ => break,
+            }
+        }
+
+        Ok(candles)
+    }
+}
+
+#[async_trait]
+impl PriceProvider for BinanceProvider {
+    fn name(&self) -> &str {
+        "binance"
+    }
+
+    async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        let Some((symbol, inverted)) = self.symbols.get(mint) else {
+            return Ok(None);
+        };
+        let candles = self.klines(symbol, interval, start_ts, end_ts).await?;
+        Ok(Some(if *inverted { invert_candles(candles) } else { candles }))
+    }
+}
+
+/// Birdeye OHLCV for any Solana mint (needs `birdeye_api_key`)
+pub struct BirdeyeProvider {
+    client: Client,
+    api_key: String,
+    limiter: Arc<RateLimiter>,
+}
+
+impl BirdeyeProvider {
+    pub fn new(client: Client, api_key: String, limiter: Arc<RateLimiter>) -> Self {
+        BirdeyeProvider { client, api_key, limiter }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for BirdeyeProvider {
+    fn name(&self) -> &str {
+        "birdeye"
+    }
+
+    async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        // Birdeye has no sub-minute candles
+        let Some(step) = interval_secs(interval).filter(|s| *s >= 60) else {
+            return Ok(None);
+        };
+        let birdeye_type = interval.replace('h', "H").replace('d', "D").replace('w', "W");
+        let mut candles = vec![];
+        let mut cursor = start_ts;
+
+        while cursor <= end_ts {
+            let url = format!(
+                "https://public-api.birdeye.so/defi/ohlcv?address={}&type={}&time_from={}&time_to={}",
+                mint, birdeye_type, cursor, end_ts
+            );
+
+            self.limiter.wait().await;
+            let resp: Value = self.client
+                .get(&url)
+                .header("X-API-KEY", &self.api_key)
+                .header("x-chain", "solana")
+                .timeout(Duration::from_secs(10))
+                .send().await?
+                .error_for_status()?
+                .json().await?;
+
+            let items = resp
+                .get("data")
+                .and_then(|d| d.get("items"))
+                .and_then(|i| i.as_array())
+                .cloned()
+                .unwrap_or_default();
+
+            let batch_len = items.len();
+            for item in items {
+                let field = |k: &str| item.get(k).and_then(|v| v.as_f64());
+                if let (Some(open_time), Some(open), Some(high), Some(low), Some(close), Some(volume)) = (
+                    item.get("unixTime").and_then(|v| v.as_u64()),
+                    field("o"),
+                    field("h"),
+                    field("l"),
+                    field("c"),
+                    field("v"),
+                ) {
+                    candles.push(Candle {
+                        open_time,
+                        open,
+                        high,
+                        low,
+                        close,
+                        volume,
+                        source: "birdeye".to_string(),
+                    });
+                }
+            }
+
+            match candles.last() {
+                Some(last) if batch_len >= MAX_CANDLES_PER_REQUEST => cursor = last.open_time + step,
+                _ => break,
+            }
+        }
+
+        if candles.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(candles))
+    }
+}
+
+/// Jupiter Price API. Spot only, so it answers just for ranges that reach the present.
+pub struct JupiterProvider {
+    client: Client,
+    limiter: Arc<RateLimiter>,
+}
+
+impl JupiterProvider {
+    pub fn new(client: Client, limiter: Arc<RateLimiter>) -> Self {
+        JupiterProvider { client, limiter }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for JupiterProvider {
+    fn name(&self) -> &str {
+        "jupiter"
+    }
+
+    async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        let step = interval_secs(interval).ok_or_else(|| format!("Unknown interval {}", interval))?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let open_time = now - now % step;
+        if open_time < start_ts || open_time > end_ts + step {
+            return Ok(None);
+        }
+
+        let url = format!("https://lite-api.jup.ag/price/v3?ids={}", mint);
+        self.limiter.wait().await;
+        let resp: Value = self.client
+            .get(&url)
+            .timeout(Duration::from_secs(10))
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        let Some(price) = resp.get(mint).and_then(|p| p.get("usdPrice")).and_then(|p| p.as_f64()) else {
+            return Ok(None);
+        };
+
+        Ok(Some(vec![Candle {
+            open_time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 0.0,
+            source: "jupiter".to_string(),
+        }]))
+    }
+}
+
+/// Candles from `<dir>/<mint>_<interval>.csv` with columns
+/// `open_time,open,high,low,close,volume` (open_time in seconds)
+pub struct LocalFileProvider {
+    dir: String,
+}
+
+impl LocalFileProvider {
+    pub fn new(dir: String) -> Self {
+        LocalFileProvider { dir }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for LocalFileProvider {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<Option<Vec<Candle>>, AnyError> {
+        let path = Path::new(&self.dir).join(format!("{}_{}.csv", mint, interval));
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+
+        let mut candles = vec![];
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            // Skips the header and any malformed rows
+            let parsed = (|| {
+                Some(Candle {
+                    open_time: fields.first()?.parse().ok()?,
+                    open: fields.get(1)?.parse().ok()?,
+                    high: fields.get(2)?.parse().ok()?,
+                    low: fields.get(3)?.parse().ok()?,
+                    close: fields.get(4)?.parse().ok()?,
+                    volume: fields.get(5).and_then(|v| v.parse().ok()).unwrap_or(0.0),
+                    source: "local".to_string(),
+                })
+            })();
+            if let Some(candle) = parsed.filter(|c| c.open_time >= start_ts && c.open_time <= end_ts) {
+                candles.push(candle);
+            }
+        }
+
+        if candles.is_empty() {
+            return Ok(None);
+        }
+        candles.sort_by_key(|c| c.open_time);
+        Ok(Some(candles))
+    }
+}
+
+/// Candles for one asset; each candle records the provider that supplied it
+#[derive(Debug, Clone)]
+pub struct PriceSeries {
+    pub interval: String,
+    pub candles: Vec<Candle>,
+}
+
+/// Tries each provider in the configured order until one has data
+pub struct CompositePriceProvider {
+    providers: Vec<Box<dyn PriceProvider>>,
+}
+
+impl CompositePriceProvider {
+    pub fn new(providers: Vec<Box<dyn PriceProvider>>) -> Self {
+        CompositePriceProvider { providers }
+    }
+
+    /// Build the chain from `price_provider_order` in config
+    pub fn from_settings(settings: &Settings) -> Self {
+        let client = Client::new();
+        let order = settings.config.price_provider_order.clone().unwrap_or_else(|| {
+            DEFAULT_PROVIDER_ORDER.iter().map(|p| p.to_string()).collect()
+        });
+
+        let offline = settings.config.offline_pricing.unwrap_or(false);
+
+        let mut providers: Vec<Box<dyn PriceProvider>> = vec![];
+        for name in order {
+            match name.as_str() {
+                // Offline runs are served from the price store and local files only
+                "binance" | "birdeye" | "jupiter" if offline => {}
+                "local" => {
+                    let dir = settings
+                        .config
+                        .local_price_dir
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_LOCAL_PRICE_DIR.to_string());
+                    providers.push(Box::new(LocalFileProvider::new(dir)));
+                }
+                "binance" => providers.push(Box::new(BinanceProvider::new(client.clone(), settings))),
+                "birdeye" if settings.birdeye_api_key.is_empty() => {
+                    println!("⚠️  Skipping birdeye price provider: no API key");
+                }
+                "birdeye" => providers.push(Box::new(BirdeyeProvider::new(
+                    client.clone(),
+                    settings.birdeye_api_key.clone(),
+                    RateLimiter::shared("birdeye", settings),
+                ))),
+                "jupiter" => providers.push(Box::new(JupiterProvider::new(
+                    client.clone(),
+                    RateLimiter::shared("jupiter", settings),
+                ))),
+                other => println!("⚠️  Unknown price provider in price_provider_order: {}", other),
+            }
+        }
+
+        CompositePriceProvider::new(providers)
+    }
+
+    /// First non-empty series for `mint`, or `None` if no provider covers it
+    pub async fn candles(
+        &self,
+        mint: &str,
+        interval: &str,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Option<PriceSeries> {
+        for provider in &self.providers {
+            match provider.candles(mint, interval, start_ts, end_ts).await {
+                Ok(Some(candles)) if !candles.is_empty() => {
+                    return Some(PriceSeries {
+                        interval: interval.to_string(),
+                        candles,
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => println!("⚠️  {} price provider failed for {}: {}", provider.name(), mint, e),
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,171 @@
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use crate::modules::amounts::{to_decimal, transfer_raw_amount, TokenAmount};
+use crate::modules::fx::{base_currency, usd_rates_at};
+use crate::modules::prices::{market_prices, SOLANA_MINT};
+use crate::modules::types::{RawTxn, Receipt, ReceiptCostBasis, ReceiptKind, Settings};
+
+const DEFAULT_STAKING_TXN_TYPES: &[&str] = &[
+    "CLAIM_REWARDS",
+    "HARVEST",
+    "HARVEST_REWARD",
+    "DISTRIBUTE_COMPRESSION_REWARDS",
+];
+const LAMPORTS_PER_SOL: f64 = 1e9;
+const SOL_DECIMALS: u8 = 9;
+/// Net SOL a receipt may cost or bring in as token account rent. Spending more is
+/// treated as a purchase, and receiving less as a rent refund rather than a receipt.
+const RENT_TOLERANCE_LAMPORTS: i64 = 10_000_000;
+
+/// Tokens and SOL received in transactions where the wallet sent no tokens, oldest first.
+/// Swaps are left to `filter_and_name_swaps`.
+pub fn incoming_receipts(transactions: &[RawTxn], settings: &Settings) -> Vec<Receipt> {
+    let wallet = settings.wallet_address.as_str();
+    let staking_types: HashSet<String> = match &settings.config.staking_txn_types {
+        Some(types) => types.iter().map(|t| t.to_uppercase()).collect(),
+        None => DEFAULT_STAKING_TXN_TYPES.iter().map(|t| t.to_string()).collect(),
+    };
+    let airdrop_types: HashSet<String> = settings
+        .config
+        .airdrop_txn_types
+        .iter()
+        .flatten()
+        .map(|t| t.to_uppercase())
+        .collect();
+    let addresses = |list: &Option<Vec<String>>| -> HashSet<String> {
+        list.iter().flatten().cloned().collect()
+    };
+    let own_wallets = addresses(&settings.config.own_wallets);
+    let airdrop_senders = addresses(&settings.config.airdrop_senders);
+
+    // Only staking and airdrop evidence makes a receipt income; anything else is a plain transfer
+    let kind = |tx: &RawTxn, from: &str| {
+        let txn_type = tx.txn_type.to_uppercase();
+        if own_wallets.contains(from) {
+            ReceiptKind::OwnTransfer
+        } else if staking_types.contains(&txn_type) {
+            ReceiptKind::Staking
+        } else if airdrop_types.contains(&txn_type) || airdrop_senders.contains(from) {
+            ReceiptKind::Airdrop
+        } else {
+            ReceiptKind::Transfer
+        }
+    };
+
+    let mut receipts = vec![];
+    for tx in transactions {
+        if tx.txn_type.eq_ignore_ascii_case("SWAP")
+            || tx.token_transfers.iter().any(|t| t.from_user_account.eq_ignore_ascii_case(wallet))
+        {
+            continue;
+        }
+        let net_lamports: i64 = tx
+            .native_transfers
+            .iter()
+            .map(|t| {
+                let received = if t.to_user_account.eq_ignore_ascii_case(wallet) { t.amount } else { 0 };
+                let sent = if t.from_user_account.eq_ignore_ascii_case(wallet) { t.amount } else { 0 };
+                received - sent
+            })
+            .sum();
+        if net_lamports < -RENT_TOLERANCE_LAMPORTS {
+            continue;
+        }
+        let timestamp = tx.timestamp.unwrap_or(0);
+
+        for transfer in tx
+            .token_transfers
+            .iter()
+            .filter(|t| t.to_user_account.eq_ignore_ascii_case(wallet) && t.token_amount > 0.0)
+        {
+            receipts.push(Receipt {
+                timestamp,
+                signature: tx.signature.clone(),
+                mint: transfer.mint.clone(),
+                amount: transfer.token_amount,
+                raw: transfer_raw_amount(tx, transfer),
+                from: transfer.from_user_account.clone(),
+                txn_type: tx.txn_type.clone(),
+                kind: kind(tx, &transfer.from_user_account),
+                cost: None,
+            });
+        }
+
+        if net_lamports > RENT_TOLERANCE_LAMPORTS {
+            let from = tx
+                .native_transfers
+                .iter()
+                .filter(|t| t.to_user_account.eq_ignore_ascii_case(wallet))
+                .max_by_key(|t| t.amount)
+                .map(|t| t.from_user_account.clone())
+                .unwrap_or_default();
+            receipts.push(Receipt {
+                timestamp,
+                signature: tx.signature.clone(),
+                mint: SOLANA_MINT.to_string(),
+                amount: net_lamports as f64 / LAMPORTS_PER_SOL,
+                raw: Some(TokenAmount::new(net_lamports as u128, SOL_DECIMALS).to_raw()),
+                txn_type: tx.txn_type.clone(),
+                kind: kind(tx, &from),
+                from,
+                cost: None,
+            });
+        }
+    }
+
+    receipts.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.signature.cmp(&b.signature)));
+    println!("📥 Found {} incoming receipts", receipts.len());
+    receipts
+}
+
+/// Market value of each receipt in the base currency when it was received
+pub async fn receipt_market_values(receipts: &[&Receipt], settings: &Settings) -> Vec<Option<Decimal>> {
+    let points: Vec<(String, u64)> = receipts.iter().map(|r| (r.mint.clone(), r.timestamp)).collect();
+    let timestamps: Vec<u64> = receipts.iter().map(|r| r.timestamp).collect();
+    let usd_prices = market_prices(&points, settings).await;
+    let rates = usd_rates_at(&base_currency(settings), &timestamps, settings).await;
+
+    receipts
+        .iter()
+        .zip(usd_prices.into_iter().zip(rates))
+        .map(|(receipt, (usd_price, rate))| {
+            usd_price.zip(rate).map(|(price, rate)| to_decimal(receipt.amount * price * rate))
+        })
+        .collect()
+}
+
+/// Set the cost basis of every receipt under `receipt_cost_basis`. Stablecoin receipts
+/// are cash and keep no cost.
+pub async fn apply_receipt_cost_basis(receipts: &mut [Receipt], settings: &Settings) {
+    let policy = settings.config.receipt_cost_basis.unwrap_or_default();
+    match policy {
+        ReceiptCostBasis::Zero => {
+            for receipt in receipts.iter_mut() {
+                receipt.cost = Some(Decimal::ZERO);
+            }
+        }
+        ReceiptCostBasis::Manual => {
+            let manual = settings.config.manual_cost_basis.as_ref();
+            for receipt in receipts.iter_mut() {
+                receipt.cost = manual.and_then(|m| m.get(&receipt.signature)).map(|&cost| to_decimal(cost));
+            }
+        }
+        ReceiptCostBasis::MarketValue => {
+            let values = {
+                let refs: Vec<&Receipt> = receipts.iter().collect();
+                receipt_market_values(&refs, settings).await
+            };
+            for (receipt, value) in receipts.iter_mut().zip(values) {
+                receipt.cost = value;
+            }
+        }
+    }
+
+    let missing = receipts
+        .iter()
+        .filter(|r| r.cost.is_none() && !settings.stablecoins.is_stable(&r.mint))
+        .count();
+    if missing > 0 {
+        println!("⚠️  {} receipts have no {:?} cost basis and are taken at zero cost", missing, policy);
+    }
+}
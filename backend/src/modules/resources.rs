@@ -0,0 +1,64 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use crate::modules::types::{PricedSwap, PricingCoverage};
+
+/// Priced and unpriced swaps among `swaps`
+pub fn pricing_coverage(swaps: &[PricedSwap]) -> PricingCoverage {
+    let priced = swaps.iter().filter(|s| s.usd_value.is_some()).count();
+    PricingCoverage {
+        swaps: swaps.len(),
+        priced,
+        unpriced: swaps.len() - priced,
+        ratio: (!swaps.is_empty()).then(|| priced as f64 / swaps.len() as f64),
+    }
+}
+
+/// Whether a mint / token name pair matches a `token` filter: the exact mint or
+/// a case-insensitive part of the name
+pub fn matches_token(filter: &str, mint: &str, name: &str) -> bool {
+    mint == filter || name.to_lowercase().contains(&filter.to_lowercase())
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap_or(0.0), b.as_f64().unwrap_or(0.0));
+            a.total_cmp(&b)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        // Missing values sort last
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => Ordering::Equal,
+    }
+}
+
+/// Serialize `items` and sort them by a top-level field, descending if `sort` starts with `-`
+pub fn sorted_values<T: Serialize>(items: &[T], sort: Option<&str>) -> Result<Vec<Value>, String> {
+    let mut values: Vec<Value> = items
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let Some(sort) = sort else {
+        return Ok(values);
+    };
+
+    let (field, descending) = match sort.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (sort, false),
+    };
+    if values.first().is_some_and(|v| v.get(field).is_none()) {
+        return Err(format!("Cannot sort by unknown field {}", field));
+    }
+    values.sort_by(|a, b| {
+        let (a, b) = (&a[field], &b[field]);
+        match (descending, a.is_null() || b.is_null()) {
+            (true, false) => compare_values(b, a),
+            _ => compare_values(a, b),
+        }
+    });
+    Ok(values)
+}
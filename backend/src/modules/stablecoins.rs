@@ -0,0 +1,112 @@
+use crate::modules::types::{Config, StablecoinConfig};
+use std::collections::HashMap;
+
+/// Built-in stablecoins: (mint, symbol, peg currency, Binance market symbol)
+const DEFAULT_STABLECOINS: &[(&str, &str, &str, Option<&str>)] = &[
+    ("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "USDC", "USD", Some("USDCUSDT")),
+    // USDT is the quote of USDCUSDT, so its market price is the inverse, with USDC as the USD reference
+    ("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", "USDT", "USD", Some("USDCUSDT")),
+    ("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo", "PYUSD", "USD", None),
+    ("7kbnvuGBxxj8AG9qp8Scn56muWGaRaFqxg1FsRp3PaFT", "UXD", "USD", None),
+    ("Ea5SjE2Y6yvCeW5dYTn7PYMuW5ikXkvbGdcmSnXeaLjS", "PAI", "USD", None),
+    ("USDH1SM1ojwWUga67PGrgFWUHibbjqMvuMaDkRJTgkX", "USDH", "USD", None),
+    ("HzwqbKZw8HxMN6bF2yFZNrht3c2iXXzpKcFu7uBEDKtr", "EURC", "EUR", None),
+    ("2VhjJ9WxaGC3EZFwJG9BDUs9KxKCAjQY4vgd1qxgYWVg", "EUROe", "EUR", None),
+    ("C4Kkr9NZU3VbyedcgutU6LKmi6MKz81sx6gRmk5pX519", "VEUR", "EUR", None),
+];
+
+/// A stablecoin known to the registry
+#[derive(Debug, Clone)]
+pub struct Stablecoin {
+    pub mint: String,
+    pub symbol: String,
+    /// ISO code of the currency the coin is pegged to, e.g. "USD" or "EUR"
+    pub peg: String,
+    /// Binance pair used when pricing at market, e.g. "USDCUSDT". Quoted in USDT, unless
+    /// the coin is the quote of the pair, as USDT is of USDCUSDT: see `market_inverted`.
+    pub market_symbol: Option<String>,
+}
+
+impl Stablecoin {
+    /// Whether the coin is the quote asset of its market pair, so its price is `1 / close`
+    pub fn market_inverted(&self) -> bool {
+        self.market_symbol
+            .as_deref()
+            .is_some_and(|pair| pair.ends_with(&self.symbol) && !pair.starts_with(&self.symbol))
+    }
+}
+
+/// Mint-based lookup of stablecoins and how to value them
+#[derive(Debug, Clone)]
+pub struct StablecoinRegistry {
+    by_mint: HashMap<String, Stablecoin>,
+    price_at_market: bool,
+}
+
+impl StablecoinRegistry {
+    /// Build the registry from the defaults, extended or overridden by `[[stablecoins]]` in config
+    pub fn from_config(config: &Config) -> Self {
+        let mut by_mint = HashMap::new();
+
+        for (mint, symbol, peg, market_symbol) in DEFAULT_STABLECOINS {
+            by_mint.insert(
+                mint.to_string(),
+                Stablecoin {
+                    mint: mint.to_string(),
+                    symbol: symbol.to_string(),
+                    peg: peg.to_string(),
+                    market_symbol: market_symbol.map(|s| s.to_string()),
+                },
+            );
+        }
+
+        for StablecoinConfig { mint, symbol, peg, market_symbol } in config.stablecoins.iter().flatten() {
+            let peg = peg.to_uppercase();
+            if peg != "USD" && peg_fx_symbol(&peg).is_none() {
+                println!("⚠️  No FX pair known for {} peg {}; {} swaps will be unpriced", symbol, peg, symbol);
+            }
+            by_mint.insert(
+                mint.clone(),
+                Stablecoin {
+                    mint: mint.clone(),
+                    symbol: symbol.clone(),
+                    peg,
+                    market_symbol: market_symbol.clone(),
+                },
+            );
+        }
+
+        StablecoinRegistry {
+            by_mint,
+            price_at_market: config.price_stables_at_market.unwrap_or(false),
+        }
+    }
+
+    pub fn get(&self, mint: &str) -> Option<&Stablecoin> {
+        self.by_mint.get(mint)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &Stablecoin> {
+        self.by_mint.values()
+    }
+
+    pub fn is_stable(&self, mint: &str) -> bool {
+        self.by_mint.contains_key(mint)
+    }
+
+    /// Whether stables should be valued at their historical market price instead of their peg
+    pub fn price_at_market(&self) -> bool {
+        self.price_at_market
+    }
+}
+
+/// Binance pair (quoted in USDT) used to convert a peg currency into USD;
+/// `None` for pegs without one, which are never treated as USD
+pub fn peg_fx_symbol(peg: &str) -> Option<&'static str> {
+    match peg {
+        "EUR" => Some("EURUSDT"),
+        "GBP" => Some("GBPUSDT"),
+        "AUD" => Some("AUDUSDT"),
+        _ => None,
+    }
+}
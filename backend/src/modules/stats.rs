@@ -0,0 +1,145 @@
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use crate::modules::prices::SOLANA_MINT;
+use crate::modules::types::{EquityPoint, MarkStatus, PerformanceStats, TokenPnl, WalletSummary};
+
+const DAY_SECS: f64 = 86400.0;
+
+/// Whether `token` is the quote asset, which every SOL-paid buy disposes of
+fn is_quote_asset(token: &TokenPnl) -> bool {
+    token.mint == SOLANA_MINT
+}
+
+/// PnL of every closed trade: the matched lots of one sell of one token, in sell order
+fn closed_trade_pnls<'a>(token_pnls: impl Iterator<Item = &'a TokenPnl>) -> Vec<(u64, Decimal)> {
+    let mut closed: BTreeMap<(u64, &str, &str), Decimal> = BTreeMap::new();
+    for trade in token_pnls.flat_map(|t| &t.trades) {
+        *closed
+            .entry((trade.sell_timestamp, &trade.sell_signature, &trade.token_mint))
+            .or_default() += trade.pnl;
+    }
+    closed.into_iter().map(|((ts, _, _), pnl)| (ts, pnl)).collect()
+}
+
+/// Largest drop of total PnL from a running peak, with the peak and trough timestamps
+fn max_drawdown(equity_curve: &[EquityPoint]) -> (Decimal, Option<u64>, Option<u64>) {
+    let mut peak: Option<&EquityPoint> = None;
+    let mut worst = (Decimal::ZERO, None, None);
+    for point in equity_curve {
+        let peak_point = match peak {
+            Some(p) if p.total_pnl >= point.total_pnl => p,
+            _ => {
+                peak = Some(point);
+                point
+            }
+        };
+        let drawdown = peak_point.total_pnl - point.total_pnl;
+        if drawdown > worst.0 {
+            worst = (drawdown, Some(peak_point.timestamp), Some(point.timestamp));
+        }
+    }
+    worst
+}
+
+fn median(mut values: Vec<u64>) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Win rate, average win and loss, profit factor, expectancy, extremes, drawdown,
+/// holding time and trade frequency of a wallet. SOL disposals by buys paid in SOL
+/// are not trades and are only counted in `quote_disposals`.
+pub fn performance_stats(token_pnls: &[TokenPnl], equity_curve: &[EquityPoint]) -> PerformanceStats {
+    let traded: Vec<&TokenPnl> = token_pnls.iter().filter(|t| !is_quote_asset(t)).collect();
+    let closed = closed_trade_pnls(traded.iter().copied());
+    let quote_disposals = closed_trade_pnls(token_pnls.iter().filter(|t| is_quote_asset(t))).len();
+    let wins: Vec<Decimal> = closed.iter().map(|(_, pnl)| *pnl).filter(|pnl| *pnl > Decimal::ZERO).collect();
+    let losses: Vec<Decimal> = closed.iter().map(|(_, pnl)| *pnl).filter(|pnl| *pnl < Decimal::ZERO).collect();
+    let gross_profit: Decimal = wins.iter().sum();
+    let gross_loss: Decimal = -losses.iter().sum::<Decimal>();
+    let average = |values: &[Decimal]| {
+        (!values.is_empty()).then(|| values.iter().sum::<Decimal>() / Decimal::from(values.len()))
+    };
+
+    let (max_drawdown, max_drawdown_peak_ts, max_drawdown_trough_ts) = max_drawdown(equity_curve);
+
+    let holding_periods = traded
+        .iter()
+        .flat_map(|t| &t.trades)
+        .filter_map(|t| t.holding_period_secs)
+        .collect();
+
+    let first_ts = traded
+        .iter()
+        .flat_map(|t| t.buys.iter().map(|b| b.timestamp).chain(t.sells.iter().map(|s| s.timestamp)))
+        .min();
+    let last_sell_ts = closed.last().map(|(ts, _)| *ts);
+    let trades_per_day = match (first_ts, last_sell_ts) {
+        (Some(first), Some(last)) => {
+            let days = ((last.saturating_sub(first)) as f64 / DAY_SECS).max(1.0);
+            Some(closed.len() as f64 / days)
+        }
+        _ => None,
+    };
+
+    PerformanceStats {
+        closed_trades: closed.len(),
+        wins: wins.len(),
+        losses: losses.len(),
+        win_rate: (!closed.is_empty()).then(|| wins.len() as f64 / closed.len() as f64),
+        average_win: average(&wins),
+        average_loss: average(&losses),
+        profit_factor: (gross_loss > Decimal::ZERO)
+            .then(|| (gross_profit / gross_loss).to_f64())
+            .flatten(),
+        expectancy: average(&closed.iter().map(|(_, pnl)| *pnl).collect::<Vec<_>>()),
+        largest_win: wins.iter().copied().max(),
+        largest_loss: losses.iter().copied().min(),
+        max_drawdown,
+        max_drawdown_peak_ts,
+        max_drawdown_trough_ts,
+        median_holding_secs: median(holding_periods),
+        trades_per_day,
+        quote_disposals,
+    }
+}
+
+/// Wallet totals plus performance statistics
+pub fn wallet_summary(
+    wallet_address: &str,
+    currency: &str,
+    token_pnls: &[TokenPnl],
+    equity_curve: &[EquityPoint],
+) -> WalletSummary {
+    let realized_pnl = token_pnls.iter().map(|t| t.realized_pnl).sum::<Decimal>();
+    let unrealized_pnl = token_pnls.iter().filter_map(|t| t.unrealized_pnl).sum::<Decimal>();
+    let open: Vec<&TokenPnl> = token_pnls
+        .iter()
+        .filter(|t| !t.remaining_amount.is_zero())
+        .collect();
+
+    WalletSummary {
+        wallet_address: wallet_address.to_string(),
+        currency: currency.to_string(),
+        realized_pnl,
+        unrealized_pnl,
+        total_pnl: realized_pnl + unrealized_pnl,
+        tokens_traded: token_pnls.len(),
+        open_positions: open.len(),
+        unpriced_positions: open
+            .iter()
+            .filter(|t| matches!(t.mark_status, MarkStatus::NoPrice | MarkStatus::NoFxRate))
+            .count(),
+        unmatched_proceeds: token_pnls.iter().map(|t| t.unmatched_proceeds).sum(),
+        stats: performance_stats(token_pnls, equity_curve),
+    }
+}
@@ -1,6 +1,9 @@
 
-use crate::modules::types::{RawTxn, Swap, NamedSwap, Settings};
-use crate::modules::utils::get_named_swaps_path;
+use crate::modules::amounts::transfer_raw_amount;
+use crate::modules::types::{RawTokenAmount, RawTxn, Swap, NamedSwap, Settings};
+use crate::modules::transactions::analysis_window;
+use crate::modules::utils::{cache_covers, get_named_swaps_path, write_cache_meta};
+use crate::modules::metadata::resolve_token_metadata;
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::Write;
@@ -9,19 +12,24 @@ use serde_json::json;
 use serde_json::Value;
 
 pub async fn filter_and_name_swaps(
-    transactions: &Vec<RawTxn>,
+    transactions: &[RawTxn],
     settings: &Settings
 ) -> Result<Vec<NamedSwap>, Box<dyn std::error::Error>> {
 
     let use_cached_swaps_raw = settings.config.use_cached_named_swaps.unwrap_or(true);
     let use_token_cache = settings.config.use_token_cache.unwrap_or(true);
     let use_jupiter_token_list = settings.config.use_jupiter_token_list.unwrap_or(true);
+    let use_metaplex_metadata = settings.config.use_metaplex_metadata.unwrap_or(false);
     let helius_api_key = &settings.helius_api_key;
     let wallet_lower = settings.wallet_address.to_lowercase();
     let swaps_path_raw = get_named_swaps_path(&settings.wallet_address);
     let write_cache_files = settings.config.write_cache_files.unwrap_or(false);
+    let (start, _) = analysis_window(settings)?;
 
-    let swaps: Vec<NamedSwap> = if use_cached_swaps_raw && Path::new(&swaps_path_raw).exists() {
+    let swaps: Vec<NamedSwap> = if use_cached_swaps_raw
+        && Path::new(&swaps_path_raw).exists()
+        && cache_covers(&swaps_path_raw, start)
+    {
         println!("♻️  Using cached swaps from {}", swaps_path_raw);
         let file = fs::read_to_string(&swaps_path_raw)?;
         serde_json::from_str(&file)?
@@ -48,6 +56,9 @@ pub async fn filter_and_name_swaps(
                     sold_amount: s.token_amount,
                     bought_mint: b.mint.clone(),
                     bought_amount: b.token_amount,
+                    sold_raw: transfer_raw_amount(tx, s),
+                    bought_raw: transfer_raw_amount(tx, b),
+                    venue: tx.source.clone(),
                 });
             }
         }
@@ -132,6 +143,14 @@ pub async fn filter_and_name_swaps(
             }
         }
 
+        if use_metaplex_metadata && !unknown_mints.is_empty() {
+            println!("Decoding Metaplex metadata for {} unknown mints...", unknown_mints.len());
+            let resolved = resolve_token_metadata(&unknown_mints, settings).await;
+            println!("✅ Resolved {} mints from Metaplex metadata", resolved.len());
+            unknown_mints.retain(|mint| !resolved.contains_key(mint));
+            mint_name_map.extend(resolved);
+        }
+
         if !unknown_mints.is_empty() {
             println!("Querying {} unknown mints via Helius...", unknown_mints.len());
             let payload = json!({ "mintAccounts": unknown_mints });
@@ -181,11 +200,14 @@ pub async fn filter_and_name_swaps(
             }
         }
 
+        let mut decimal_mismatches = HashSet::new();
         let enriched: Vec<NamedSwap> = raw_swaps
             .into_iter()
             .map(|s| {
                 let (sold_token_name, sold_decimals) = mint_name_map.get(&s.sold_mint).cloned().unwrap_or(("UNKNOWN".to_string(), None));
                 let (bought_token_name, bought_decimals) = mint_name_map.get(&s.bought_mint).cloned().unwrap_or(("UNKNOWN".to_string(), None));
+                let sold_decimals = verify_decimals(&s.sold_mint, sold_decimals, s.sold_raw.as_ref(), &s.signature, &mut decimal_mismatches);
+                let bought_decimals = verify_decimals(&s.bought_mint, bought_decimals, s.bought_raw.as_ref(), &s.signature, &mut decimal_mismatches);
                 NamedSwap {
                     timestamp: s.timestamp,
                     signature: s.signature,
@@ -197,13 +219,21 @@ pub async fn filter_and_name_swaps(
                     bought_token_name,
                     bought_amount: s.bought_amount,
                     bought_decimals,
+                    sold_raw: s.sold_raw,
+                    bought_raw: s.bought_raw,
+                    venue: s.venue,
                 }
             })
             .collect();
 
+        if !decimal_mismatches.is_empty() {
+            println!("⚠️  Data quality: {} mints have registry decimals that disagree with their transactions", decimal_mismatches.len());
+        }
+
         if write_cache_files {
             let mut file = File::create(&swaps_path_raw)?;
             write!(file, "{}", serde_json::to_string_pretty(&enriched)?)?;
+            write_cache_meta(&swaps_path_raw, start)?;
             println!("✅ Enriched swaps written to {}", swaps_path_raw);
         } else {
             println!("Filtered and named {} swaps.", enriched.len());
@@ -214,3 +244,26 @@ pub async fn filter_and_name_swaps(
 
     Ok(swaps)
 }
+
+/// Decimals of a swap leg: the transaction's if it reported a raw amount, else the registry's.
+/// A disagreement is logged once per mint as a data-quality warning.
+fn verify_decimals(
+    mint: &str,
+    registry: Option<u8>,
+    raw: Option<&RawTokenAmount>,
+    signature: &str,
+    mismatches: &mut HashSet<String>,
+) -> Option<u8> {
+    let Some(raw) = raw else {
+        return registry;
+    };
+    if let Some(registry) = registry {
+        if registry != raw.decimals && mismatches.insert(mint.to_string()) {
+            println!(
+                "⚠️  Data quality: {} has {} decimals in the token registry but {} in transaction {}",
+                mint, registry, raw.decimals, signature
+            );
+        }
+    }
+    Some(raw.decimals)
+}
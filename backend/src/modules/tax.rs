@@ -0,0 +1,401 @@
+use chrono::{Datelike, Months, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use crate::modules::pnl_series::parse_timezone;
+use crate::modules::receipts::receipt_market_values;
+use crate::modules::transactions::AnyError;
+use crate::modules::types::{
+    Disposal, IncomeEvent, Receipt, ReceiptKind, Settings, TaxRegime, TaxReport, TaxTerm, TaxYearSummary,
+    TokenPnl,
+};
+
+const DEFAULT_HOLDING_PERIOD_MONTHS: u32 = 12;
+pub const DEFAULT_TAX_REPORT_DIR: &str = "reports";
+
+/// Jurisdiction rules a report is computed with
+pub struct TaxRules {
+    pub regime: TaxRegime,
+    pub holding_period_months: u32,
+    pub tz: Tz,
+}
+
+impl TaxRules {
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        Ok(TaxRules {
+            regime: settings.config.tax_regime.unwrap_or_default(),
+            holding_period_months: settings
+                .config
+                .tax_holding_period_months
+                .unwrap_or(DEFAULT_HOLDING_PERIOD_MONTHS),
+            tz: parse_timezone(settings.config.timezone.as_deref())?,
+        })
+    }
+
+    fn date(&self, ts: u64) -> NaiveDate {
+        self.tz
+            .timestamp_opt(ts as i64, 0)
+            .single()
+            .map(|dt| dt.date_naive())
+            .unwrap_or_default()
+    }
+
+    /// Held longer than the holding period: sold after the same calendar day
+    /// `holding_period_months` after the acquisition
+    fn term(&self, acquired: Option<NaiveDate>, sold: NaiveDate) -> TaxTerm {
+        let Some(acquired) = acquired else {
+            return TaxTerm::Unknown;
+        };
+        let held_long = acquired
+            .checked_add_months(Months::new(self.holding_period_months))
+            .is_some_and(|end| sold > end);
+        match (self.regime, held_long) {
+            (_, false) => TaxTerm::ShortTerm,
+            (TaxRegime::ShortLongTerm, true) => TaxTerm::LongTerm,
+            (TaxRegime::TaxFreeAfterHolding, true) => TaxTerm::TaxFree,
+        }
+    }
+}
+
+/// Every matched lot as a disposal, plus every sell no lot covered as a disposal of
+/// unknown term at zero cost, in sell order
+pub fn disposals(token_pnls: &[TokenPnl], rules: &TaxRules) -> Vec<Disposal> {
+    let mut disposals: Vec<Disposal> = token_pnls
+        .iter()
+        .flat_map(|t| &t.trades)
+        .map(|trade| {
+            let acquired = trade.buy_timestamp.map(|ts| rules.date(ts));
+            let sold = rules.date(trade.sell_timestamp);
+            Disposal {
+                token_name: trade.token_name.clone(),
+                mint: trade.token_mint.clone(),
+                amount: trade.amount,
+                buy_signature: trade.buy_signature.clone(),
+                sell_signature: trade.sell_signature.clone(),
+                date_acquired: acquired.map(|d| d.to_string()),
+                date_sold: sold.to_string(),
+                tax_year: sold.year(),
+                proceeds: trade.proceeds,
+                cost_basis: trade.cost,
+                gain: trade.pnl,
+                term: rules.term(acquired, sold),
+            }
+        })
+        .collect();
+
+    for token in token_pnls {
+        for sell in &token.unmatched_disposals {
+            let sold = rules.date(sell.timestamp);
+            disposals.push(Disposal {
+                token_name: token.token.clone(),
+                mint: token.mint.clone(),
+                amount: sell.amount,
+                buy_signature: None,
+                sell_signature: sell.signature.clone(),
+                date_acquired: None,
+                date_sold: sold.to_string(),
+                tax_year: sold.year(),
+                proceeds: sell.proceeds,
+                cost_basis: Decimal::ZERO,
+                gain: sell.proceeds,
+                term: rules.term(None, sold),
+            });
+        }
+    }
+    disposals.sort_by(|a, b| {
+        a.date_sold
+            .cmp(&b.date_sold)
+            .then(a.sell_signature.cmp(&b.sell_signature))
+    });
+    disposals
+}
+
+/// Airdrop and staking receipts valued in the base currency at the time they were received.
+/// Plain transfers, including those from the owner's other wallets, are not income.
+pub async fn income_events(
+    receipts: &[Receipt],
+    token_pnls: &[TokenPnl],
+    rules: &TaxRules,
+    settings: &Settings,
+) -> Vec<IncomeEvent> {
+    let income: Vec<&Receipt> = receipts
+        .iter()
+        .filter(|r| matches!(r.kind, ReceiptKind::Airdrop | ReceiptKind::Staking))
+        .collect();
+    let names: HashMap<&str, &str> = token_pnls
+        .iter()
+        .map(|t| (t.mint.as_str(), t.token.as_str()))
+        .collect();
+
+    let values = receipt_market_values(&income, settings).await;
+
+    income
+        .into_iter()
+        .zip(values)
+        .map(|(receipt, value)| {
+            let date = rules.date(receipt.timestamp);
+            IncomeEvent {
+                kind: receipt.kind,
+                token_name: names.get(receipt.mint.as_str()).unwrap_or(&"UNKNOWN").to_string(),
+                mint: receipt.mint.clone(),
+                amount: receipt.amount,
+                signature: receipt.signature.clone(),
+                date: date.to_string(),
+                tax_year: date.year(),
+                value,
+            }
+        })
+        .collect()
+}
+
+fn year_summary(years: &mut BTreeMap<i32, TaxYearSummary>, year: i32) -> &mut TaxYearSummary {
+    years.entry(year).or_insert_with(|| TaxYearSummary {
+        year,
+        ..Default::default()
+    })
+}
+
+/// Disposals and income with per-year totals, limited to `year` if set
+pub fn tax_report(
+    wallet_address: &str,
+    currency: &str,
+    rules: &TaxRules,
+    mut disposals: Vec<Disposal>,
+    mut income: Vec<IncomeEvent>,
+    year: Option<i32>,
+) -> TaxReport {
+    if let Some(year) = year {
+        disposals.retain(|d| d.tax_year == year);
+        income.retain(|i| i.tax_year == year);
+    }
+
+    let mut years: BTreeMap<i32, TaxYearSummary> = BTreeMap::new();
+    for d in &disposals {
+        let y = year_summary(&mut years, d.tax_year);
+        y.disposals += 1;
+        y.proceeds += d.proceeds;
+        y.cost_basis += d.cost_basis;
+        match d.term {
+            TaxTerm::ShortTerm => y.short_term_gain += d.gain,
+            TaxTerm::LongTerm => y.long_term_gain += d.gain,
+            TaxTerm::TaxFree => y.tax_free_gain += d.gain,
+            TaxTerm::Unknown => y.unknown_term_gain += d.gain,
+        }
+        if d.term != TaxTerm::TaxFree {
+            y.taxable_gain += d.gain;
+        }
+    }
+    for i in &income {
+        let y = year_summary(&mut years, i.tax_year);
+        match (i.kind, i.value) {
+            (_, None) => y.unpriced_income += 1,
+            (ReceiptKind::Staking, Some(value)) => y.staking_income += value,
+            (_, Some(value)) => y.airdrop_income += value,
+        }
+    }
+
+    TaxReport {
+        wallet_address: wallet_address.to_string(),
+        currency: currency.to_string(),
+        regime: rules.regime,
+        holding_period_months: rules.holding_period_months,
+        timezone: rules.tz.name().to_string(),
+        years: years.into_values().collect(),
+        disposals,
+        income,
+    }
+}
+
+fn term_label(term: TaxTerm) -> &'static str {
+    match term {
+        TaxTerm::ShortTerm => "short",
+        TaxTerm::LongTerm => "long",
+        TaxTerm::TaxFree => "tax_free",
+        TaxTerm::Unknown => "unknown",
+    }
+}
+
+/// `YYYY-MM-DD` as the `MM/DD/YYYY` Form 8949 expects
+fn form_date(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| d.format("%m/%d/%Y").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+/// Write the Form-8949-style disposals, the per-year summary and the income section as CSV
+/// into `dir`, returning the written paths
+pub fn write_tax_report(report: &TaxReport, dir: &str) -> Result<Vec<String>, AnyError> {
+    fs::create_dir_all(dir)?;
+    let path = |name: &str| {
+        Path::new(dir)
+            .join(format!("tax_{}_{}.csv", name, report.wallet_address))
+            .to_string_lossy()
+            .to_string()
+    };
+
+    let form_path = path("8949");
+    let mut form = csv::Writer::from_path(&form_path)?;
+    form.write_record([
+        "Term",
+        "(a) Description of property",
+        "(b) Date acquired",
+        "(c) Date sold or disposed of",
+        "(d) Proceeds",
+        "(e) Cost or other basis",
+        "(f) Code",
+        "(g) Amount of adjustment",
+        "(h) Gain or (loss)",
+        "Sell signature",
+    ])?;
+    for d in &report.disposals {
+        form.write_record([
+            term_label(d.term).to_string(),
+            format!("{} {}", d.amount, d.token_name),
+            d.date_acquired.as_deref().map(form_date).unwrap_or_else(|| "UNKNOWN".to_string()),
+            form_date(&d.date_sold),
+            format!("{:.2}", d.proceeds),
+            format!("{:.2}", d.cost_basis),
+            String::new(),
+            String::new(),
+            format!("{:.2}", d.gain),
+            d.sell_signature.clone(),
+        ])?;
+    }
+    form.flush()?;
+
+    let summary_path = path("summary");
+    let mut summary = csv::Writer::from_path(&summary_path)?;
+    summary.write_record([
+        "year",
+        "currency",
+        "disposals",
+        "proceeds",
+        "cost_basis",
+        "short_term_gain",
+        "long_term_gain",
+        "tax_free_gain",
+        "unknown_term_gain",
+        "taxable_gain",
+        "airdrop_income",
+        "staking_income",
+        "unpriced_income",
+    ])?;
+    for y in &report.years {
+        summary.write_record([
+            y.year.to_string(),
+            report.currency.clone(),
+            y.disposals.to_string(),
+            format!("{:.2}", y.proceeds),
+            format!("{:.2}", y.cost_basis),
+            format!("{:.2}", y.short_term_gain),
+            format!("{:.2}", y.long_term_gain),
+            format!("{:.2}", y.tax_free_gain),
+            format!("{:.2}", y.unknown_term_gain),
+            format!("{:.2}", y.taxable_gain),
+            format!("{:.2}", y.airdrop_income),
+            format!("{:.2}", y.staking_income),
+            y.unpriced_income.to_string(),
+        ])?;
+    }
+    summary.flush()?;
+
+    let income_path = path("income");
+    let mut income = csv::Writer::from_path(&income_path)?;
+    income.write_record(["date", "kind", "token", "mint", "amount", "value", "currency", "signature"])?;
+    for i in &report.income {
+        let kind = match i.kind {
+            ReceiptKind::Airdrop => "airdrop",
+            ReceiptKind::Staking => "staking",
+            ReceiptKind::OwnTransfer => "own_transfer",
+            ReceiptKind::Transfer => "transfer",
+        };
+        income.write_record([
+            i.date.clone(),
+            kind.to_string(),
+            i.token_name.clone(),
+            i.mint.clone(),
+            i.amount.to_string(),
+            i.value.map(|v| format!("{:.2}", v)).unwrap_or_default(),
+            report.currency.clone(),
+            i.signature.clone(),
+        ])?;
+    }
+    income.flush()?;
+
+    println!("🧾 Tax report written to {}", dir);
+    Ok(vec![form_path, summary_path, income_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::pnl::{calculate_direct_token_pnl, PnlOptions};
+    use crate::modules::stablecoins::StablecoinRegistry;
+    use crate::modules::types::{Config, CostBasisMethod, PricedSwap};
+    use serde_json::json;
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const TOKEN: &str = "TokenMint1111111111111111111111111111111111";
+
+    fn swap(signature: &str, timestamp: u64, sold: (&str, u128), bought: (&str, u128), value: f64) -> PricedSwap {
+        let leg = |mint: &str, raw: u128| {
+            let decimals = if mint == USDC { 6 } else { 9 };
+            json!({ "tokenAmount": raw.to_string(), "decimals": decimals })
+        };
+        serde_json::from_value(json!({
+            "timestamp": timestamp,
+            "signature": signature,
+            "sold_mint": sold.0,
+            "sold_token_name": sold.0,
+            "sold_amount": 0.0,
+            "sold_decimals": null,
+            "bought_mint": bought.0,
+            "bought_token_name": bought.0,
+            "bought_amount": 0.0,
+            "bought_decimals": null,
+            "sold_raw": leg(sold.0, sold.1),
+            "bought_raw": leg(bought.0, bought.1),
+            "pricing_method": "test",
+            "binance_sol_usd_price": null,
+            "usd_value": value,
+            "base_value": value,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn unmatched_sells_are_reported_as_unknown_term_disposals() {
+        let swaps = vec![
+            swap("buy", 1_700_000_000, (USDC, 10_000_000), (TOKEN, 1_000_000_000), 10.0),
+            swap("sell", 1_700_086_400, (TOKEN, 2_000_000_000), (USDC, 30_000_000), 30.0),
+        ];
+        let config: Config = serde_json::from_value(json!({})).unwrap();
+        let registry = StablecoinRegistry::from_config(&config);
+        let options = PnlOptions {
+            stablecoins: &registry,
+            currency: "USD".to_string(),
+            method: CostBasisMethod::Fifo,
+            lot_selections: None,
+        };
+        let token_pnls = calculate_direct_token_pnl(&swaps, &[], &options);
+        let rules = TaxRules {
+            regime: TaxRegime::ShortLongTerm,
+            holding_period_months: 12,
+            tz: chrono_tz::UTC,
+        };
+
+        let disposals = disposals(&token_pnls, &rules);
+        assert_eq!(disposals.len(), 2);
+        let unmatched = disposals.iter().find(|d| d.term == TaxTerm::Unknown).unwrap();
+        assert_eq!(unmatched.buy_signature, None);
+        assert_eq!(unmatched.cost_basis, Decimal::ZERO);
+        assert_eq!(unmatched.proceeds, Decimal::from(15));
+
+        let report = tax_report("wallet", "USD", &rules, disposals, vec![], None);
+        assert_eq!(report.years[0].proceeds, Decimal::from(30));
+        assert_eq!(report.years[0].unknown_term_gain, Decimal::from(15));
+        assert_eq!(report.years[0].taxable_gain, Decimal::from(20));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::modules::types::{RawTxn, Settings};
+use crate::modules::utils::{cache_covers, write_cache_meta};
 
 use std::{
     fs::{self, File},
@@ -8,19 +9,56 @@ use std::{
 
 use tokio::time::{sleep, Duration};
 use reqwest::Client;
-use chrono::{Utc, Duration as ChronoDuration};
+use chrono::Utc;
 
 /// Convenience error type alias
 pub type AnyError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
+pub const DEFAULT_WINDOW: &str = "30d";
+
+/// Length in seconds of a window like `30d`, `2w` or `12h`; `None` for `all`
+pub fn parse_window(window: &str) -> Result<Option<u64>, String> {
+    let window = window.trim().to_lowercase();
+    if window == "all" {
+        return Ok(None);
+    }
+    let invalid = || format!("Invalid window {}: expected e.g. 30d, 2w, 12h or all", window);
+    let unit_secs: u64 = match window.chars().last() {
+        Some('h') => 3600,
+        Some('d') => 86400,
+        Some('w') => 7 * 86400,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = window[..window.len() - 1].parse().map_err(|_| invalid())?;
+    count.checked_mul(unit_secs).map(Some).ok_or_else(invalid)
+}
+
+/// Time range `[start, end]` the wallet is analyzed over: the configured `window`
+/// (30 days by default) ending at `as_of` or now. `start` is `None` for `all`.
+pub fn analysis_window(settings: &Settings) -> Result<(Option<u64>, u64), String> {
+    let end = settings
+        .config
+        .as_of
+        .unwrap_or_else(|| Utc::now().timestamp() as u64);
+    let length = parse_window(settings.config.window.as_deref().unwrap_or(DEFAULT_WINDOW))?;
+    Ok((length.map(|l| end.saturating_sub(l)), end))
+}
+
+/// Transactions of the wallet within `analysis_window`, newest first. A cached file
+/// is only used if it was fetched back to the start of the window.
 pub async fn get_transactions(settings: &Settings) -> Result<Vec<RawTxn>, AnyError> {
+    let (start, end) = analysis_window(settings)?;
     let helius_api_key = &settings.helius_api_key;
     let use_cached_txns = settings.config.use_cached_txns.unwrap_or(false);
     let write_cache_files = settings.config.write_cache_files.unwrap_or(false);
 
     let transactions_path = format!("cache/transactions_{}.json", settings.wallet_address);
+    let cache_usable = Path::new(&transactions_path).exists() && cache_covers(&transactions_path, start);
+    if use_cached_txns && !cache_usable && Path::new(&transactions_path).exists() {
+        println!("♻️  Cached transactions do not reach back to the analysis window, fetching again");
+    }
 
-    let transactions: Vec<RawTxn> = if use_cached_txns && Path::new(&transactions_path).exists() {
+    let mut transactions: Vec<RawTxn> = if use_cached_txns && cache_usable {
         println!("♻️  Using cached transactions from {}", transactions_path);
         let file = File::open(&transactions_path)
             .map_err(|e| format!("Failed to open cache file: {}", e))?;
@@ -34,8 +72,6 @@ pub async fn get_transactions(settings: &Settings) -> Result<Vec<RawTxn>, AnyErr
         let mut all = Vec::new();
         let mut before: Option<String> = None;
 
-        let thirty_days_ago = Utc::now().timestamp() - ChronoDuration::days(30).num_seconds();
-
         loop {
             let mut url = format!(
                 "https://api.helius.xyz/v0/addresses/{}/transactions?api-key={}",
@@ -57,11 +93,11 @@ pub async fn get_transactions(settings: &Settings) -> Result<Vec<RawTxn>, AnyErr
 
             let filtered_batch: Vec<RawTxn> = batch
                 .into_iter()
-                .filter(|tx| tx.timestamp.unwrap_or(0) as i64 >= thirty_days_ago)
+                .filter(|tx| start.is_none_or(|start| tx.timestamp.unwrap_or(0) >= start))
                 .collect();
 
             if filtered_batch.is_empty() {
-                println!("⏹️  Stopped: no transactions in last 30 days.");
+                println!("⏹️  Stopped: no more transactions in the analysis window.");
                 break;
             }
 
@@ -80,6 +116,8 @@ pub async fn get_transactions(settings: &Settings) -> Result<Vec<RawTxn>, AnyErr
             write!(file, "{}", serde_json::to_string_pretty(&all)
                 .map_err(|e| format!("Failed to serialize JSON: {}", e))?)
                 .map_err(|e| format!("Failed to write to cache file: {}", e))?;
+            write_cache_meta(&transactions_path, start)
+                .map_err(|e| format!("Failed to write to cache file: {}", e))?;
         } else {
             println!("Fetched {} transactions.", all.len());
         }
@@ -87,5 +125,9 @@ pub async fn get_transactions(settings: &Settings) -> Result<Vec<RawTxn>, AnyErr
         all
     };
 
+    transactions.retain(|tx| {
+        let ts = tx.timestamp.unwrap_or(0);
+        start.is_none_or(|start| ts >= start) && ts <= end
+    });
     Ok(transactions)
 }
@@ -1,4 +1,8 @@
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::modules::amounts::TokenAmount;
+use crate::modules::stablecoins::StablecoinRegistry;
 
 /// Configuration loaded from `config.toml`
 #[derive(Debug, Deserialize)]
@@ -10,6 +14,162 @@ pub struct Config {
     pub use_jupiter_token_list: Option<bool>,
     pub fifo: Option<bool>,
     pub write_cache_files: Option<bool>,
+    pub price_stables_at_market: Option<bool>,
+    pub stablecoins: Option<Vec<StablecoinConfig>>,
+    pub use_metaplex_metadata: Option<bool>,
+    pub solana_rpc_url: Option<String>,
+    pub account_snapshot_dir: Option<String>,
+    pub price_provider_order: Option<Vec<String>>,
+    pub local_price_dir: Option<String>,
+    pub price_store_dir: Option<String>,
+    pub offline_pricing: Option<bool>,
+    pub price_tolerance_secs: Option<u64>,
+    pub price_sampling: Option<PriceSampling>,
+    pub compare_price_sampling: Option<bool>,
+    pub base_currency: Option<String>,
+    pub fx_provider_order: Option<Vec<String>>,
+    pub local_fx_dir: Option<String>,
+    pub use_implied_prices: Option<bool>,
+    pub pool_implied_prices: Option<bool>,
+    pub price_fetch_concurrency: Option<usize>,
+    pub provider_requests_per_sec: Option<HashMap<String, f64>>,
+    pub slippage_threshold_bps: Option<f64>,
+    /// Takes precedence over `fifo`
+    pub cost_basis_method: Option<CostBasisMethod>,
+    /// Buy signatures each sell signature consumes, for `specific_lot`
+    pub lot_selections: Option<HashMap<String, Vec<String>>>,
+    /// Unix timestamp to value open positions at instead of now
+    pub as_of: Option<u64>,
+    /// How far back to analyze from `as_of`, e.g. "30d", "2w", "12h" or "all" (default "30d")
+    pub window: Option<String>,
+    /// IANA timezone for PnL bucket boundaries, e.g. "Europe/Berlin" (UTC if unset)
+    pub timezone: Option<String>,
+    pub tax_regime: Option<TaxRegime>,
+    /// Holding period after which a disposal is long term / tax free (default 12)
+    pub tax_holding_period_months: Option<u32>,
+    pub tax_report_dir: Option<String>,
+    /// Helius transaction types whose incoming transfers are staking income
+    pub staking_txn_types: Option<Vec<String>>,
+    /// Helius transaction types whose incoming transfers are airdrops
+    pub airdrop_txn_types: Option<Vec<String>>,
+    /// Distributor accounts whose transfers are airdrops
+    pub airdrop_senders: Option<Vec<String>>,
+    /// Other wallets of the same owner; transfers from them are not income
+    pub own_wallets: Option<Vec<String>>,
+    pub receipt_cost_basis: Option<ReceiptCostBasis>,
+    /// Cost basis in the base currency per receipt signature, for `manual`
+    pub manual_cost_basis: Option<HashMap<String, f64>>,
+}
+
+/// Cost basis of tokens received without a swap
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptCostBasis {
+    Zero,
+    /// Market value when received
+    #[default]
+    MarketValue,
+    /// From `manual_cost_basis`; receipts missing there open at zero cost and are
+    /// listed in `unpriced_signatures`
+    Manual,
+}
+
+/// How the holding period of a disposal affects its taxation
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxRegime {
+    /// Short term up to the holding period, long term after it (US)
+    #[default]
+    ShortLongTerm,
+    /// Taxable up to the holding period, tax free after it (e.g. Germany)
+    TaxFreeAfterHolding,
+}
+
+/// Which buy lots a sell is matched against
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CostBasisMethod {
+    #[default]
+    Fifo,
+    Lifo,
+    /// Highest unit cost first
+    Hifo,
+    /// Every sell is valued at the weighted average cost of all open lots
+    AverageCost,
+    /// Lots chosen per sell via `lot_selections`, FIFO for the rest
+    SpecificLot,
+}
+
+/// How a price is read from the candles around a swap
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSampling {
+    /// Close of the 1m candle nearest to the swap
+    #[default]
+    Close,
+    /// Linear interpolation between open and close of the swap's 1m candle
+    Linear,
+    /// Midpoint of the swap's 1m candle range, (high + low) / 2
+    OhlcMid,
+    /// Volume-weighted price from 1s klines around the swap, linear 1m otherwise
+    #[serde(rename = "vwap_1s")]
+    Vwap1s,
+}
+
+impl PriceSampling {
+    pub const ALL: [PriceSampling; 4] = [
+        PriceSampling::Close,
+        PriceSampling::Linear,
+        PriceSampling::OhlcMid,
+        PriceSampling::Vwap1s,
+    ];
+
+    /// Value stored in `PricedSwap.interpolation`
+    pub fn interpolation(&self) -> &'static str {
+        match self {
+            PriceSampling::Close => "nearest_close",
+            PriceSampling::Linear => "linear",
+            PriceSampling::OhlcMid => "ohlc_mid",
+            PriceSampling::Vwap1s => "vwap_1s",
+        }
+    }
+
+    /// Mode to use on 1m candles; VWAP falls back to linear interpolation
+    pub fn for_1m(&self) -> PriceSampling {
+        match self {
+            PriceSampling::Vwap1s => PriceSampling::Linear,
+            other => *other,
+        }
+    }
+
+    /// `PricedSwap.pricing_method` for a base method such as "binance_1m"
+    pub fn method_name(&self, base: &str) -> String {
+        match self {
+            PriceSampling::Close => base.to_string(),
+            other => format!("{}_{}", base, other.interpolation()),
+        }
+    }
+}
+
+/// Realized PnL of a wallet when priced with one sampling mode
+#[derive(Debug, Serialize)]
+pub struct SamplingComparison {
+    pub sampling: PriceSampling,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub realized_pnl: Decimal,
+    /// Difference to the configured `price_sampling`
+    #[serde(with = "rust_decimal::serde::float")]
+    pub delta: Decimal,
+    pub unpriced_swaps: usize,
+}
+
+/// Extra stablecoin entry from `[[stablecoins]]` in `config.toml`
+#[derive(Debug, Deserialize, Clone)]
+pub struct StablecoinConfig {
+    pub mint: String,
+    pub symbol: String,
+    pub peg: String,
+    pub market_symbol: Option<String>,
 }
 
 pub struct Settings {
@@ -17,6 +177,7 @@ pub struct Settings {
     pub helius_api_key: String,
     pub birdeye_api_key: String,
     pub wallet_address: String,
+    pub stablecoins: StablecoinRegistry,
 }
 
 
@@ -29,6 +190,14 @@ pub struct Swap {
     pub sold_amount: f64,
     pub bought_mint: String,
     pub bought_amount: f64,
+    /// Exact amounts as reported by the transaction, when available
+    #[serde(default)]
+    pub sold_raw: Option<RawTokenAmount>,
+    #[serde(default)]
+    pub bought_raw: Option<RawTokenAmount>,
+    /// Venue the swap was routed through (Helius `source`)
+    #[serde(default)]
+    pub venue: String,
 }
 
 /// Final swap structure including resolved token names
@@ -44,6 +213,12 @@ pub struct NamedSwap {
     pub bought_token_name: String,
     pub bought_amount: f64,
     pub bought_decimals: Option<u8>,
+    #[serde(default)]
+    pub sold_raw: Option<RawTokenAmount>,
+    #[serde(default)]
+    pub bought_raw: Option<RawTokenAmount>,
+    #[serde(default)]
+    pub venue: String,
 }
 
 
@@ -60,14 +235,86 @@ pub struct PricedSwap {
     pub bought_token_name: String,
     pub bought_amount: f64,
     pub bought_decimals: Option<u8>,
+    #[serde(default)]
+    pub sold_raw: Option<RawTokenAmount>,
+    #[serde(default)]
+    pub bought_raw: Option<RawTokenAmount>,
+    #[serde(default)]
+    pub venue: String,
     pub pricing_method: String,
+    /// Which leg `usd_value` was derived from
+    #[serde(default)]
+    pub priced_leg: Option<PricedLeg>,
     pub binance_sol_usd_price: Option<f64>,
     pub usd_value: Option<f64>,
+    /// Open time of the candle the price was taken from
+    #[serde(default)]
+    pub price_timestamp: Option<u64>,
+    /// Distance between the swap and `price_timestamp`
+    #[serde(default)]
+    pub price_distance_secs: Option<u64>,
+    /// How the price was derived from the candle, e.g. "nearest_close" or "peg"
+    #[serde(default)]
+    pub interpolation: Option<String>,
+    #[serde(default)]
+    pub confidence: PriceConfidence,
+    /// Why `usd_value` is `None`
+    #[serde(default)]
+    pub unpriced_reason: Option<String>,
+    /// Reporting currency of `base_value`
+    #[serde(default)]
+    pub base_currency: String,
+    /// `usd_value` converted at the USD/base rate of the swap's day
+    #[serde(default)]
+    pub base_value: Option<f64>,
+    /// Units of `base_currency` per 1 USD used for `base_value`
+    #[serde(default)]
+    pub fx_rate: Option<f64>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceConfidence {
+    High,
+    Medium,
+    Low,
+    #[default]
+    Unpriced,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PricedLeg {
+    Sold,
+    Bought,
+}
+
+impl PricedLeg {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PricedLeg::Sold => "sold",
+            PricedLeg::Bought => "bought",
+        }
+    }
+}
 
 
 
+
+/// OHLCV candle in USD, `open_time` in seconds
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Candle {
+    pub open_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    /// Provider that supplied the candle, e.g. "binance"
+    #[serde(default)]
+    pub source: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct RawTxn {
     pub signature: String,
@@ -79,10 +326,39 @@ pub struct RawTxn {
     pub description: String,
     #[serde(rename = "type")]
     pub txn_type: String,
+    /// Program or aggregator Helius attributes the transaction to, e.g. "JUPITER"
+    #[serde(default)]
+    pub source: String,
     #[serde(rename = "nativeTransfers")]
     pub native_transfers: Vec<NativeTransfer>,
     #[serde(rename = "tokenTransfers")]
     pub token_transfers: Vec<TokenTransfer>,
+    #[serde(rename = "accountData", default)]
+    pub account_data: Vec<AccountData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountData {
+    pub account: String,
+    #[serde(rename = "tokenBalanceChanges", default)]
+    pub token_balance_changes: Vec<TokenBalanceChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenBalanceChange {
+    #[serde(rename = "userAccount")]
+    pub user_account: String,
+    pub mint: String,
+    #[serde(rename = "rawTokenAmount")]
+    pub raw_token_amount: RawTokenAmount,
+}
+
+/// Integer amount in base units as Helius reports it, e.g. `{ "tokenAmount": "-1500000", "decimals": 6 }`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawTokenAmount {
+    #[serde(rename = "tokenAmount")]
+    pub token_amount: String,
+    pub decimals: u8,
 }
 
 
@@ -102,62 +378,580 @@ pub struct TokenTransfer {
     #[serde(rename = "toUserAccount")]
     pub to_user_account: String,
     pub mint: String,
+    /// UI amount, already scaled by the mint's decimals
     #[serde(rename = "tokenAmount")]
     pub token_amount: f64,
+    #[serde(rename = "rawTokenAmount", default)]
+    pub raw_token_amount: Option<RawTokenAmount>,
 }
 
-#[derive(Serialize)]
+/// One buy lot (or part of it) matched against one sell.
+/// Buy fields are `None` for the part of a sell no open lot covered.
+#[derive(Debug, Serialize, Clone)]
 pub struct Trade {
     pub token_mint: String,
     pub token_name: String,
-    pub buy_signature: String,
+    pub buy_signature: Option<String>,
     pub sell_signature: String,
-    pub buy_timestamp: u64,
+    pub buy_timestamp: Option<u64>,
     pub sell_timestamp: u64,
-    pub amount: f64,
-    pub cost_usd: f64,
-    pub proceeds_usd: f64,
-    pub pnl_usd: f64,
-    pub holding_period_secs: u64,
-}
-
-#[derive(Debug, Clone)]
-pub struct InventoryEntry {
-    pub amount: f64,
-    pub price_per_token: f64,
-    pub total_usd: f64,
-    pub timestamp: u64,
-    pub signature: String,
+    pub amount: TokenAmount,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub cost: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub proceeds: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub pnl: Decimal,
+    pub holding_period_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct TokenPnl {
     pub token: String,
+    pub mint: String,
+    /// Currency of every value below (the configured `base_currency`)
+    pub currency: String,
+    /// Every buy, in order
     pub buys: Vec<BuyPart>,
     pub sells: Vec<SellPart>,
-    pub realized_pnl: f64,
-    pub total_bought: f64,
-    pub total_sold: f64,
-    pub remaining_amount: f64,
-    pub average_cost_usd: f64,
+    /// What is left of the buys after sells, with the remaining cost basis
+    pub open_lots: Vec<BuyPart>,
+    /// Lot-level matches; their `pnl` adds up to `realized_pnl`
+    pub trades: Vec<Trade>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub realized_pnl: Decimal,
+    pub total_bought: TokenAmount,
+    pub total_sold: TokenAmount,
+    pub remaining_amount: TokenAmount,
+    /// Cost basis per unit of the open lots
+    #[serde(with = "rust_decimal::serde::float")]
+    pub average_cost: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub open_cost: Decimal,
+    /// Price per unit the open lots were valued at
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub mark_price: Option<Decimal>,
+    /// Open time of the candle `mark_price` came from
+    pub mark_timestamp: Option<u64>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub current_value: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub unrealized_pnl: Option<Decimal>,
+    /// `realized_pnl + unrealized_pnl`; `None` while the open lots have no price
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub total_pnl: Option<Decimal>,
+    pub mark_status: MarkStatus,
+    /// Swaps of this token that could not be priced and are excluded from the PnL
+    pub unpriced_signatures: Vec<String>,
+    /// Sells, or parts of them, no open lot covered; they have no trade and no PnL
+    pub unmatched_disposals: Vec<SellPart>,
+    /// Proceeds of `unmatched_disposals`, left out of `realized_pnl`
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unmatched_proceeds: Decimal,
+    pub cost_basis_method: CostBasisMethod,
+}
+
+/// Whether the open lots of a token could be valued
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkStatus {
+    /// Nothing left to value
+    #[default]
+    Closed,
+    Priced,
+    /// Open lots but no provider has a recent price; likely a dead token
+    NoPrice,
+    /// The token has a price but there is no rate to convert it into the base currency
+    NoFxRate,
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Debug, Serialize, Clone)]
 pub struct BuyPart {
     pub timestamp: u64,
-    pub amount: f64,
-    pub cost_usd: f64,
+    pub signature: String,
+    pub amount: TokenAmount,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub cost: Decimal,
+    /// How the lot was acquired if not by a swap
+    pub receipt: Option<ReceiptKind>,
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Debug, Serialize, Clone)]
 pub struct SellPart {
     pub timestamp: u64,
+    pub signature: String,
+    pub amount: TokenAmount,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub proceeds: Decimal,
+}
+
+/// Length of the buckets in a PnL series
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketSize {
+    #[default]
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+/// PnL realized within one bucket and the state of open positions at its end
+#[derive(Debug, Serialize, Clone)]
+pub struct PnlBucket {
+    /// Local date the bucket starts on (`YYYY-MM-DD`)
+    pub label: String,
+    pub start_ts: u64,
+    pub end_ts: u64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub realized_pnl: Decimal,
+    /// Matched trades closed in the bucket
+    pub trades: usize,
+    /// Unrealized PnL of the positions open at `end_ts` that have a price
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unrealized_pnl: Decimal,
+    /// Open positions at `end_ts` without a price, left out of `unrealized_pnl`
+    pub unpriced_positions: usize,
+    /// No base-currency rate at `end_ts`; the priced positions are left out of
+    /// `unrealized_pnl` and not counted in `unpriced_positions`
+    pub fx_rate_missing: bool,
+}
+
+/// Cumulative performance of the wallet at the end of a bucket
+#[derive(Debug, Serialize, Clone)]
+pub struct EquityPoint {
+    pub timestamp: u64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub cumulative_realized: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unrealized_pnl: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total_pnl: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PnlSeries {
+    pub currency: String,
+    pub bucket: BucketSize,
+    pub timezone: String,
+    pub buckets: Vec<PnlBucket>,
+    pub equity_curve: Vec<EquityPoint>,
+}
+
+/// Body of `POST /api/pnl/series`
+#[derive(Deserialize)]
+pub struct PnlSeriesRequest {
+    #[serde(flatten)]
+    pub pnl: PnlRequest,
+    pub bucket: Option<BucketSize>,
+    /// Overrides `timezone` from config
+    pub timezone: Option<String>,
+}
+
+/// Trading statistics of a wallet. A closed trade is one sell of one token, with the
+/// PnL of every lot it consumed.
+#[derive(Debug, Serialize, Default)]
+pub struct PerformanceStats {
+    /// Closed trades of every token but SOL
+    pub closed_trades: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub win_rate: Option<f64>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub average_win: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub average_loss: Option<Decimal>,
+    /// Gross profit over gross loss; `None` without losing trades
+    pub profit_factor: Option<f64>,
+    /// Average PnL per closed trade
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub expectancy: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub largest_win: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub largest_loss: Option<Decimal>,
+    /// Largest drop of total PnL from a previous peak of the daily equity curve
+    #[serde(with = "rust_decimal::serde::float")]
+    pub max_drawdown: Decimal,
+    pub max_drawdown_peak_ts: Option<u64>,
+    pub max_drawdown_trough_ts: Option<u64>,
+    /// Median holding period of the matched lots
+    pub median_holding_secs: Option<u64>,
+    /// Closed trades per day between the first swap and the last sell
+    pub trades_per_day: Option<f64>,
+    /// SOL sells, mostly SOL spent on buys; not counted as trades
+    pub quote_disposals: usize,
+}
+
+/// Response of `POST /api/summary`
+#[derive(Debug, Serialize)]
+pub struct WalletSummary {
+    pub wallet_address: String,
+    pub currency: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub realized_pnl: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unrealized_pnl: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total_pnl: Decimal,
+    pub tokens_traded: usize,
+    pub open_positions: usize,
+    /// Open positions without a price or FX rate, left out of `unrealized_pnl`
+    pub unpriced_positions: usize,
+    /// Proceeds of sells no lot covered, left out of every PnL figure
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unmatched_proceeds: Decimal,
+    pub stats: PerformanceStats,
+}
+
+/// One step of the PnL ledger of a token, with the state after it
+#[derive(Debug, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum ExplainStep {
+    /// A lot was opened by a swap or a receipt
+    Buy {
+        lot: BuyPart,
+        #[serde(with = "rust_decimal::serde::float")]
+        unit_cost: Decimal,
+        open_amount: TokenAmount,
+        #[serde(with = "rust_decimal::serde::float")]
+        open_cost: Decimal,
+    },
+    /// A sell consumed `lots`; a lot without `buy_signature` is the part no lot covered
+    Sell {
+        timestamp: u64,
+        signature: String,
+        amount: TokenAmount,
+        #[serde(with = "rust_decimal::serde::float")]
+        proceeds: Decimal,
+        lots: Vec<Trade>,
+        #[serde(with = "rust_decimal::serde::float")]
+        cost_basis: Decimal,
+        #[serde(with = "rust_decimal::serde::float")]
+        pnl: Decimal,
+        #[serde(with = "rust_decimal::serde::float")]
+        realized_pnl: Decimal,
+        open_amount: TokenAmount,
+        #[serde(with = "rust_decimal::serde::float")]
+        open_cost: Decimal,
+    },
+}
+
+/// Step-by-step derivation of the PnL of one token in one wallet
+#[derive(Debug, Serialize)]
+pub struct TokenExplanation {
+    pub wallet_address: String,
+    pub mint: String,
+    pub token: String,
+    pub currency: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub steps: Vec<ExplainStep>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub realized_pnl: Decimal,
+    pub remaining_amount: TokenAmount,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub open_cost: Decimal,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub unrealized_pnl: Option<Decimal>,
+    /// Swaps or receipts left out of the ledger or taken at zero cost for lack of a price
+    pub unpriced_signatures: Vec<String>,
+    pub unmatched_disposals: Vec<SellPart>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unmatched_proceeds: Decimal,
+}
+
+/// Query of `GET /api/wallets/:addr/tokens/:mint/explain`
+#[derive(Deserialize)]
+pub struct ExplainQuery {
+    pub cost_basis_method: Option<CostBasisMethod>,
+    pub as_of: Option<u64>,
+}
+
+/// Query of the `GET /api/wallets/:addr/...` resource routes
+#[derive(Deserialize)]
+pub struct WalletQuery {
+    /// Overrides `window` from config
+    pub window: Option<String>,
+    pub cost_basis_method: Option<CostBasisMethod>,
+    pub base_currency: Option<String>,
+    pub as_of: Option<u64>,
+    /// Mint, or case-insensitive part of a token name
+    pub token: Option<String>,
+    /// Field to sort by, descending with a leading `-`, e.g. `-realized_pnl`
+    pub sort: Option<String>,
+}
+
+/// Share of swaps with a USD value
+#[derive(Debug, Serialize)]
+pub struct PricingCoverage {
+    pub swaps: usize,
+    pub priced: usize,
+    pub unpriced: usize,
+    pub ratio: Option<f64>,
+}
+
+/// Metadata sent with every resource response
+#[derive(Debug, Serialize)]
+pub struct ResponseMeta {
+    pub wallet_address: String,
+    pub resource: String,
+    pub window: String,
+    pub start_ts: Option<u64>,
+    pub end_ts: u64,
+    pub currency: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub token: Option<String>,
+    pub sort: Option<String>,
+    pub count: usize,
+    /// `None` for resources that are not priced
+    pub pricing_coverage: Option<PricingCoverage>,
+    pub generated_at: u64,
+}
+
+/// Stage of an analysis job, in pipeline order
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    Queued,
+    Fetching,
+    Naming,
+    Pricing,
+    Pnl,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStage {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStage::Done | JobStage::Failed | JobStage::Cancelled)
+    }
+}
+
+/// Progress of a background analysis job, as returned by `GET /api/jobs/:id`
+#[derive(Debug, Serialize, Clone)]
+pub struct JobStatus {
+    pub id: String,
+    pub wallet_address: String,
+    pub stage: JobStage,
+    pub transactions: Option<usize>,
+    pub swaps: Option<usize>,
+    pub priced_swaps: Option<usize>,
+    pub unpriced_swaps: Option<usize>,
+    pub receipts: Option<usize>,
+    pub tokens: Option<usize>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// `{ trades: [...] }` as from `/api/pnl`, once done
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// Holding-period class of a disposal
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxTerm {
+    ShortTerm,
+    LongTerm,
+    TaxFree,
+    /// Sold without a matching buy lot, so the acquisition date is unknown
+    Unknown,
+}
+
+/// One matched lot (or unmatched sell remainder) as reported for taxes
+#[derive(Debug, Serialize, Clone)]
+pub struct Disposal {
+    pub token_name: String,
+    pub mint: String,
+    pub amount: TokenAmount,
+    pub buy_signature: Option<String>,
+    pub sell_signature: String,
+    /// Local dates in the configured timezone, `YYYY-MM-DD`
+    pub date_acquired: Option<String>,
+    pub date_sold: String,
+    pub tax_year: i32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub proceeds: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub cost_basis: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub gain: Decimal,
+    pub term: TaxTerm,
+}
+
+/// Why tokens arrived in the wallet without a swap
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptKind {
+    Airdrop,
+    Staking,
+    /// From one of `own_wallets`
+    OwnTransfer,
+    /// Any other incoming transfer, e.g. an exchange withdrawal; not income
+    Transfer,
+}
+
+/// Tokens received in a transaction where the wallet sent nothing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Receipt {
+    pub timestamp: u64,
+    pub signature: String,
+    pub mint: String,
+    pub amount: f64,
+    #[serde(default)]
+    pub raw: Option<RawTokenAmount>,
+    pub from: String,
+    pub txn_type: String,
+    pub kind: ReceiptKind,
+    /// Cost basis in the base currency under `receipt_cost_basis`; `None` if it could not be set
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub cost: Option<Decimal>,
+}
+
+/// Airdrop or staking receipt valued at the market price when received
+#[derive(Debug, Serialize, Clone)]
+pub struct IncomeEvent {
+    pub kind: ReceiptKind,
+    pub token_name: String,
+    pub mint: String,
+    pub amount: f64,
+    pub signature: String,
+    pub date: String,
+    pub tax_year: i32,
+    /// `None` if no market price was found
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub value: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct TaxYearSummary {
+    pub year: i32,
+    pub disposals: usize,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub proceeds: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub cost_basis: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub short_term_gain: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub long_term_gain: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub tax_free_gain: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unknown_term_gain: Decimal,
+    /// Every gain except `tax_free_gain`
+    #[serde(with = "rust_decimal::serde::float")]
+    pub taxable_gain: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub airdrop_income: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub staking_income: Decimal,
+    /// Income events without a market price, left out of the income totals
+    pub unpriced_income: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaxReport {
+    pub wallet_address: String,
+    pub currency: String,
+    pub regime: TaxRegime,
+    pub holding_period_months: u32,
+    pub timezone: String,
+    pub years: Vec<TaxYearSummary>,
+    pub disposals: Vec<Disposal>,
+    pub income: Vec<IncomeEvent>,
+}
+
+/// Body of `POST /api/tax`
+#[derive(Deserialize)]
+pub struct TaxReportRequest {
+    #[serde(flatten)]
+    pub pnl: PnlRequest,
+    /// Only report this tax year
+    pub year: Option<i32>,
+}
+
+/// Execution price of a swap's traded token compared with the market price at that moment
+#[derive(Debug, Serialize, Clone)]
+pub struct SwapExecution {
+    pub signature: String,
+    pub timestamp: u64,
+    pub venue: String,
+    /// Token the execution price is for: the leg the swap was not priced from
+    pub mint: String,
+    pub token_name: String,
+    /// "buy" if the wallet received the token, "sell" if it gave it away
+    pub side: String,
     pub amount: f64,
-    pub proceeds_usd: f64,
+    /// Per token, in the report's `currency`
+    pub execution_price: f64,
+    pub market_price: f64,
+    /// Cost against the market price; negative if the fill beat the market
+    pub slippage_bps: f64,
+    pub slippage: f64,
+    /// Above `slippage_threshold_bps`
+    pub flagged: bool,
+}
+
+/// Slippage of a set of swaps, weighted by their market value
+#[derive(Debug, Serialize, Clone)]
+pub struct SlippageSummary {
+    /// Token name, venue or month (`YYYY-MM`)
+    pub key: String,
+    pub swaps: usize,
+    pub flagged: usize,
+    pub market_value: f64,
+    pub slippage: f64,
+    pub avg_slippage_bps: f64,
+    pub max_slippage_bps: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExecutionReport {
+    /// Base currency of every price and value in the report
+    pub currency: String,
+    pub threshold_bps: f64,
+    pub swaps: Vec<SwapExecution>,
+    pub by_token: Vec<SlippageSummary>,
+    pub by_venue: Vec<SlippageSummary>,
+    pub by_month: Vec<SlippageSummary>,
+    /// Priced swaps without a market price for their traded token or a rate for their day
+    pub unmeasured_signatures: Vec<String>,
 }
 
 #[derive(Deserialize)]
 pub struct PnlRequest {
     pub wallet_address: String,
+    /// Overrides `cost_basis_method` from config
+    pub cost_basis_method: Option<CostBasisMethod>,
+    /// Overrides `lot_selections` from config
+    pub lot_selections: Option<HashMap<String, Vec<String>>>,
+    /// Overrides `as_of` from config
+    pub as_of: Option<u64>,
+}
+
+/// USD price of a token implied by one priced swap: the priced value divided
+/// by the amount of the other leg
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImpliedPrice {
+    pub mint: String,
+    pub timestamp: u64,
+    pub signature: String,
+    pub wallet: String,
+    pub usd_price: f64,
+    pub amount: f64,
+    /// `pricing_method` of the swap the price was derived from
+    pub source_method: String,
+}
+
+/// Query for `GET /api/implied_prices/:mint`
+#[derive(Deserialize)]
+pub struct ImpliedPriceQuery {
+    /// Only prices from this wallet unless `pooled` is set
+    pub wallet_address: Option<String>,
+    pub pooled: Option<bool>,
+    pub interval: Option<String>,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
 }
 
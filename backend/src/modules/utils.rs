@@ -1,5 +1,6 @@
 use std::{env, path::PathBuf};
 use crate::modules::types::Config;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub fn get_project_root() -> PathBuf {
@@ -19,6 +20,41 @@ pub fn get_priced_swaps_path(wallet: &str) -> String {
     format!("cache/swaps_priced_{}.json", wallet)
 }
 
+pub fn get_implied_prices_path() -> String {
+    "cache/implied_prices.json".to_string()
+}
+
+/// Oldest timestamp a wallet cache was built back to; `None` for the full history
+#[derive(Serialize, Deserialize)]
+struct CacheMeta {
+    start: Option<u64>,
+}
+
+fn cache_meta_path(cache_path: &str) -> String {
+    format!("{}.meta", cache_path.trim_end_matches(".json"))
+}
+
+/// Whether the cache at `cache_path` reaches back to `start` (`None` = full history).
+/// Caches written without this record are treated as not covering anything.
+pub fn cache_covers(cache_path: &str, start: Option<u64>) -> bool {
+    let Some(meta) = std::fs::read_to_string(cache_meta_path(cache_path))
+        .ok()
+        .and_then(|content| serde_json::from_str::<CacheMeta>(&content).ok())
+    else {
+        return false;
+    };
+    match (meta.start, start) {
+        (None, _) => true,
+        (Some(cached), Some(start)) => cached <= start,
+        (Some(_), None) => false,
+    }
+}
+
+/// Record next to the cache at `cache_path` how far back it reaches
+pub fn write_cache_meta(cache_path: &str, start: Option<u64>) -> std::io::Result<()> {
+    std::fs::write(cache_meta_path(cache_path), serde_json::to_string(&CacheMeta { start })?)
+}
+
 pub fn load_config () -> Result<Config, Box<dyn std::error::Error>> {
         // Load config
     let settings = config::Config::builder()